@@ -0,0 +1,46 @@
+# https://github.com/emmanueltouzery/projectpad2
+# shell integration for ppcli: control-space to run
+# requires bash 4.4+ for mapfile -d
+__ppcli_run() {
+    local pieces cmd cur_folder
+    # split by NUL, keep the empty pieces, the run folder may be empty
+    mapfile -d '' -t pieces < <(ppcli --shell-integration)
+    case "${pieces[0]}" in
+        R) # R == run
+            cur_folder=$(pwd)
+            if [[ -n ${pieces[2]} ]]; then
+                cmd="cd ${pieces[2]} && ${pieces[1]}"
+            else
+                cmd="${pieces[1]}"
+            fi
+            echo -e "\e[3m$cmd\e[0m" # print with italics because it wasn't really _typed_
+            history -s "$cmd"
+            if [[ -n ${pieces[3]} ]]; then
+                # environment variables for the command (for instance to get
+                # the ssh password from ppcli). Use a subshell so that they
+                # don't leak in the shell
+                (eval "export ${pieces[3]}"; eval "$cmd") </dev/tty
+            else
+                eval "$cmd" </dev/tty
+            fi
+            cd "$cur_folder"
+            # the pieces after the environment are temporary files holding secrets
+            # (for instance ssh keys), which we must remove now that the command exited
+            if (( ${#pieces[@]} > 4 )); then
+                rm -f -- "${pieces[@]:4}"
+            fi
+            ;;
+        P) # P == print to the prompt
+            READLINE_LINE="${pieces[1]}"
+            READLINE_POINT=${#READLINE_LINE}
+            ;;
+        C) # C == Copy to the clipboard
+            if [[ -n $WAYLAND_DISPLAY ]]; then
+                printf '%s' "${pieces[1]}" | wl-copy
+            else
+                printf '%s' "${pieces[1]}" | xsel --clipboard
+            fi
+            ;;
+    esac
+}
+bind -x '"\C-@": __ppcli_run'
@@ -0,0 +1,41 @@
+# https://github.com/emmanueltouzery/projectpad2
+# shell integration for ppcli: control-space to run
+function ppcli-run
+    # split by NUL, keep the empty pieces, the run folder may be empty
+    set -l pieces (ppcli --shell-integration | string split0)
+    switch "$pieces[1]"
+        case R # R == run
+            set -l cur_folder (pwd)
+            if test -n "$pieces[3]"
+                set cmd "cd $pieces[3] && $pieces[2]"
+            else
+                set cmd "$pieces[2]"
+            end
+            echo -e "\e[3m$cmd\e[0m" # print with italics because it wasn't really _typed_
+            history append -- "$cmd" 2>/dev/null
+            if test -n "$pieces[4]"
+                # environment variables for the command (for instance to get
+                # the ssh password from ppcli). Prefix the command with them
+                # so that they don't leak in the shell
+                eval "env $pieces[4] sh -c "(string escape -- $cmd) </dev/tty
+            else
+                eval "$cmd" </dev/tty
+            end
+            cd "$cur_folder"
+            # the pieces after the environment are temporary files holding secrets
+            # (for instance ssh keys), which we must remove now that the command exited
+            if test (count $pieces) -gt 4
+                rm -f -- $pieces[5..-1]
+            end
+            commandline -f repaint
+        case P # P == print to the prompt
+            commandline -r -- "$pieces[2]"
+        case C # C == Copy to the clipboard
+            if set -q WAYLAND_DISPLAY
+                printf '%s' "$pieces[2]" | wl-copy
+            else
+                printf '%s' "$pieces[2]" | xsel --clipboard
+            end
+    end
+end
+bind -k nul ppcli-run
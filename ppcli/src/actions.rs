@@ -1,46 +1,431 @@
-use crate::database::{ItemOfInterest, ItemType};
+use crate::askpass;
+use crate::config::UserAction;
+use crate::database::{AuthKey, DatabaseKind, ItemOfInterest, ItemType, JumpHost, ServerInfo};
+use crate::secret_files::{self, SecretFile};
 use projectpadsql::models::{InterestType, RunOn, ServerAccessType};
 use std::borrow::Cow;
+use std::path::PathBuf;
 
+#[derive(Clone, Copy)]
 enum SshCommandType {
     Ssh,
     Scp,
 }
 
+// the server we open the ssh connection to
+struct SshTarget<'a> {
+    username: &'a str,
+    ip: &'a str,
+    auth_key: Option<&'a AuthKey>,
+    jump_hosts: &'a [JumpHost],
+}
+
+fn ssh_target(server_info: &ServerInfo) -> SshTarget {
+    SshTarget {
+        username: &server_info.server_username,
+        ip: &server_info.server_ip,
+        auth_key: server_info.server_auth_key.as_ref(),
+        jump_hosts: &server_info.jump_hosts,
+    }
+}
+
+// for ssh tunnel servers we don't connect to the server itself, but
+// to the server we're tunneling through, which is the last jump host.
+fn ssh_tunnel_target(server_info: &ServerInfo) -> Option<SshTarget> {
+    server_info
+        .jump_hosts
+        .split_last()
+        .map(|(tunnel_host, jump_hosts)| SshTarget {
+            username: &tunnel_host.server_username,
+            ip: &tunnel_host.server_ip,
+            auth_key: tunnel_host.server_auth_key.as_ref(),
+            jump_hosts,
+        })
+}
+
+fn item_ssh_target(item: &ItemOfInterest) -> Option<SshTarget> {
+    item.server_info.as_ref().and_then(|si| {
+        if si.server_access_type == ServerAccessType::SrvAccessSshTunnel {
+            ssh_tunnel_target(si)
+        } else {
+            Some(ssh_target(si))
+        }
+    })
+}
+
+fn split_host_port(ip: &str) -> Option<(&str, Option<&str>)> {
+    match ip.split(':').collect::<Vec<&str>>()[..] {
+        [addr, port] => Some((addr, Some(port))),
+        [addr] => Some((addr, None)),
+        _ => None,
+    }
+}
+
+// the ssh or scp command and its options, and separately the user@host destination
+fn ssh_command_parts<'a>(
+    target: &'a SshTarget,
+    ssh_command_type: SshCommandType,
+) -> Option<(Vec<Cow<'a, str>>, String)> {
+    let (addr, port) = split_host_port(target.ip)?;
+    let user_param = if target.username.is_empty() {
+        Cow::Borrowed("")
+    } else {
+        Cow::Owned(format!("{}@", target.username))
+    };
+    let mut params = vec![Cow::Borrowed(match ssh_command_type {
+        SshCommandType::Ssh => "ssh",
+        SshCommandType::Scp => "scp",
+    })];
+    match (ssh_command_type, port.unwrap_or("22")) {
+        // don't pass in the -p/-P parameter if we're using the default port
+        // I sometimes use alt-enter to edit a ssh command into a scp command
+        // and the -p/-P difference gets in the way...
+        (_, "22") => {}
+        (SshCommandType::Ssh, port) => params.push(Cow::Owned(format!("-p {}", port))),
+        (SshCommandType::Scp, port) => params.push(Cow::Owned(format!("-P {}", port))),
+    }
+    if target
+        .jump_hosts
+        .iter()
+        .any(|h| h.server_auth_key.is_some())
+    {
+        // ssh doesn't allow to specify identity files for jump hosts (-J)
+        // on the command-line, chain the hops through ProxyCommand instead
+        params.push(Cow::Owned(format!(
+            "-o {}",
+            shell_words::quote(&format!(
+                "ProxyCommand={}",
+                proxy_command(target.jump_hosts, addr, port.unwrap_or("22"))?
+            ))
+        )));
+    } else if !target.jump_hosts.is_empty() {
+        // without keys of their own, the jump hosts authenticate
+        // through the ssh agent, the ssh config or askpass
+        params.push(Cow::Owned(format!(
+            "-J {}",
+            target
+                .jump_hosts
+                .iter()
+                .map(|h| if h.server_username.is_empty() {
+                    h.server_ip.clone()
+                } else {
+                    format!("{}@{}", h.server_username, h.server_ip)
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        )));
+    }
+    if let Some(auth_key) = target.auth_key {
+        params.push(Cow::Owned(format!(
+            "-i {}",
+            shell_words::quote(&auth_key_path(auth_key).to_string_lossy())
+        )));
+    }
+    Some((params, format!("{}{}", user_param, addr)))
+}
+
+// the ssh command connecting to the last jump host, and forwarding its
+// stdin/stdout to the destination. The hops before it are handled the
+// same way, so with several hops with keys the proxy commands are nested.
+fn proxy_command(jump_hosts: &[JumpHost], dest_addr: &str, dest_port: &str) -> Option<String> {
+    let (hop, previous_hops) = jump_hosts.split_last()?;
+    prepare_ssh_command(
+        &SshTarget {
+            username: &hop.server_username,
+            ip: &hop.server_ip,
+            auth_key: hop.server_auth_key.as_ref(),
+            jump_hosts: previous_hops,
+        },
+        SshCommandType::Ssh,
+        &[format!("-W {}:{}", dest_addr, dest_port)],
+    )
+}
+
+fn prepare_ssh_command(
+    target: &SshTarget,
+    ssh_command_type: SshCommandType,
+    extra_params: &[String],
+) -> Option<String> {
+    let (mut params, destination) = ssh_command_parts(target, ssh_command_type)?;
+    params.extend(extra_params.iter().map(|p| Cow::Borrowed(p.as_str())));
+    params.push(Cow::Owned(destination));
+    Some(params.join(" "))
+}
+
 fn try_prepare_ssh_command(
     item: &ItemOfInterest,
     ssh_command_type: SshCommandType,
 ) -> Option<String> {
-    // TODO must be a cleaner way to express this...
-    if let Some([addr, port]) = match item
+    prepare_ssh_command(
+        &ssh_target(item.server_info.as_ref().unwrap()),
+        ssh_command_type,
+        &[],
+    )
+}
+
+fn auth_key_path(auth_key: &AuthKey) -> PathBuf {
+    secret_files::run_file_path(&auth_key.file_name)
+}
+
+fn uses_ssh(action: &Action) -> bool {
+    action.secrets != CommandSecrets::None
+}
+
+/// The files which must be written on disk for the command of the action
+/// to work, for instance the ssh private key of the server.
+pub fn get_secret_files(action: &Action) -> Vec<SecretFile> {
+    let item = &action.item;
+    if !uses_ssh(action) {
+        return vec![];
+    }
+    // the keys of the server we connect to, and of the jump hosts on the way
+    let mut files: Vec<_> = item_ssh_target(item)
+        .map(|t| {
+            t.auth_key
+                .into_iter()
+                .chain(
+                    t.jump_hosts
+                        .iter()
+                        .filter_map(|h| h.server_auth_key.as_ref()),
+                )
+                .map(|auth_key| SecretFile {
+                    path: auth_key_path(auth_key),
+                    contents: auth_key.contents.clone(),
+                    executable: false,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(db_info) = item
+        .db_info
+        .as_ref()
+        .filter(|_| action.secrets == CommandSecrets::SshAndDatabase)
+    {
+        if let Some(client) = &db_info.db_client {
+            files.push(SecretFile {
+                path: db_password_file_path(item, client.kind),
+                contents: db_password_file_contents(client.kind, &db_info.db_password).into_bytes(),
+                executable: false,
+            });
+        }
+    }
+    if item
         .server_info
         .as_ref()
-        .unwrap()
-        .server_ip
-        .split(':')
-        .collect::<Vec<&str>>()[..]
+        .and_then(|si| si.askpass_ref.as_ref())
+        .is_some()
     {
-        [addr, port] => Some([addr, port]),
-        [addr] => Some([addr, "22"]),
-        _ => None,
-    } {
-        let username = &item.server_info.as_ref().unwrap().server_username;
-        let user_param = if username.is_empty() {
-            Cow::Borrowed("")
+        files.extend(askpass::askpass_script());
+    }
+    files
+}
+
+/// The environment variables to set for the command of the action,
+/// so that ssh gets the stored passwords from ppcli.
+pub fn get_command_env(action: &Action) -> Vec<(&'static str, String)> {
+    match action
+        .item
+        .server_info
+        .as_ref()
+        .and_then(|si| si.askpass_ref.as_ref())
+        .filter(|_| uses_ssh(action))
+    {
+        Some(askpass_ref) => {
+            let script_path = askpass::askpass_script_path().to_string_lossy().to_string();
+            vec![
+                ("SSH_ASKPASS", script_path),
+                ("SSH_ASKPASS_REQUIRE", "force".to_string()),
+                (askpass::ASKPASS_REF_ENV_VAR, askpass_ref.clone()),
+            ]
+        }
+        None => vec![],
+    }
+}
+
+fn try_prepare_port_forward(item: &ItemOfInterest, remote_port: Option<i32>) -> Option<String> {
+    let server_info = item.server_info.as_ref()?;
+    let (target, forwarded_host, forwarded_port, local_port) =
+        if server_info.server_access_type == ServerAccessType::SrvAccessSshTunnel {
+            // we connect to the tunnel server, and forward to the server
+            let (host, port) = split_host_port(&server_info.server_ip)?;
+            let forwarded_port = remote_port
+                .or_else(|| port.and_then(|p| p.parse().ok()))
+                .or(server_info.ssh_tunnel_port)?;
+            let local_port = if remote_port.is_some() {
+                forwarded_port
+            } else {
+                server_info.ssh_tunnel_port.unwrap_or(forwarded_port)
+            };
+            (
+                ssh_tunnel_target(server_info)?,
+                host,
+                forwarded_port,
+                local_port,
+            )
         } else {
-            Cow::Owned(format!("{}@", username))
+            // we connect to the server itself, and forward
+            // to a port that's open locally on that server
+            let port = remote_port?;
+            (ssh_target(server_info), "localhost", port, port)
         };
-        Some(match (ssh_command_type, port) {
-            // don't pass in the -p/-P parameter if we're using the default port
-            // I sometimes use alt-enter to edit a ssh command into a scp command
-            // and the -p/-P difference gets in the way...
-            (SshCommandType::Ssh, "22") => format!("ssh {}{}", user_param, addr),
-            (SshCommandType::Scp, "22") => format!("scp {}{}", user_param, addr),
-            (SshCommandType::Ssh, _) => format!("ssh -p {} {}{}", port, user_param, addr),
-            (SshCommandType::Scp, _) => format!("scp -P {} {}{}", port, user_param, addr),
-        })
+    prepare_ssh_command(
+        &target,
+        SshCommandType::Ssh,
+        &[
+            "-N".to_string(),
+            format!("-L {}:{}:{}", local_port, forwarded_host, forwarded_port),
+        ],
+    )
+}
+
+fn get_value_forward_port(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    if let Some(forward_command) = try_prepare_port_forward(item, None) {
+        Cow::Owned(forward_command)
+    } else {
+        Cow::Borrowed(&item.item_text)
+    }
+}
+
+fn get_value_forward_db(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    let db_port = item
+        .db_info
+        .as_ref()
+        .and_then(|db| db.db_client.as_ref())
+        .map(|c| c.port());
+    if let Some(forward_command) = try_prepare_port_forward(item, db_port) {
+        Cow::Owned(forward_command)
     } else {
-        None
+        Cow::Borrowed(&item.item_text)
+    }
+}
+
+fn db_password_file_path(item: &ItemOfInterest, kind: DatabaseKind) -> PathBuf {
+    secret_files::run_file_path(&format!(
+        "db-{}.{}",
+        item.id,
+        match kind {
+            DatabaseKind::PostgreSql => "pgpass",
+            DatabaseKind::MySql => "my.cnf",
+            DatabaseKind::SqlServer => "sqlcmd",
+        }
+    ))
+}
+
+fn db_password_file_contents(kind: DatabaseKind, password: &str) -> String {
+    match kind {
+        // https://www.postgresql.org/docs/current/libpq-pgpass.html
+        DatabaseKind::PostgreSql => format!(
+            "*:*:*:*:{}\n",
+            password.replace('\\', "\\\\").replace(':', "\\:")
+        ),
+        // https://dev.mysql.com/doc/refman/8.0/en/option-files.html
+        DatabaseKind::MySql => format!(
+            "[client]\npassword=\"{}\"\n",
+            password.replace('\\', "\\\\").replace('"', "\\\"")
+        ),
+        // sqlcmd only supports the password through the environment,
+        // we'll read this file in a shell and export the variable
+        DatabaseKind::SqlServer => password.to_string(),
+    }
+}
+
+// the shell script running the database client, connecting to host & port.
+// the password is handed through a file or the environment, never through
+// the command-line.
+fn db_client_script(item: &ItemOfInterest, host: &str, port: &str) -> Option<String> {
+    let db_info = item.db_info.as_ref()?;
+    let client = db_info.db_client.as_ref()?;
+    let password_file =
+        shell_words::quote(&db_password_file_path(item, client.kind).to_string_lossy())
+            .into_owned();
+    let db_name = shell_words::quote(&db_info.db_name).into_owned();
+    let username = shell_words::quote(&db_info.db_username).into_owned();
+    Some(match client.kind {
+        DatabaseKind::PostgreSql => format!(
+            "PGPASSFILE={} {} -h {} -p {} -U {} {}",
+            password_file,
+            client.command(),
+            host,
+            port,
+            username,
+            db_name
+        ),
+        DatabaseKind::MySql => format!(
+            "{} --defaults-extra-file={} -h {} -P {} -u {} {}",
+            client.command(),
+            password_file,
+            host,
+            port,
+            username,
+            db_name
+        ),
+        DatabaseKind::SqlServer => format!(
+            "SQLCMDPASSWORD=$(cat {}) {} -S {},{} -U {} -d {}",
+            password_file,
+            client.command(),
+            host,
+            port,
+            username,
+            db_name
+        ),
+    })
+}
+
+/// A local port that's free now, for `ppcli --free-local-port`
+pub fn free_local_port() -> std::io::Result<u16> {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .map(|a| a.port())
+}
+
+fn try_prepare_db_client(item: &ItemOfInterest) -> Option<String> {
+    let db_info = item.db_info.as_ref()?;
+    let server_info = item.server_info.as_ref()?;
+    let db_port = db_info.db_client.as_ref()?.port();
+    let script = if db_info.db_direct_access
+        && server_info.server_access_type != ServerAccessType::SrvAccessSshTunnel
+    {
+        let (host, _) = split_host_port(&server_info.server_ip)?;
+        db_client_script(item, host, &db_port.to_string())?
+    } else {
+        // open a ssh tunnel in the background. The 'sleep' keeps it alive
+        // long enough for the client to connect, and then ssh waits for the
+        // forwarded connection to be closed before exiting. The local port
+        // is picked when the command runs, by ppcli itself.
+        let exe = std::env::current_exe().ok()?;
+        let (target, forwarded_host) =
+            if server_info.server_access_type == ServerAccessType::SrvAccessSshTunnel {
+                (
+                    ssh_tunnel_target(server_info)?,
+                    split_host_port(&server_info.server_ip)?.0,
+                )
+            } else {
+                (ssh_target(server_info), "localhost")
+            };
+        let ssh_command = prepare_ssh_command(
+            &target,
+            SshCommandType::Ssh,
+            &[
+                "-f".to_string(),
+                "-o ExitOnForwardFailure=yes".to_string(),
+                format!("-L $p:{}:{}", forwarded_host, db_port),
+            ],
+        )?;
+        format!(
+            "p=$({} --free-local-port) && {} sleep 10 && {}",
+            shell_words::quote(&exe.to_string_lossy()),
+            ssh_command,
+            db_client_script(item, "localhost", "$p")?
+        )
+    };
+    Some(format!("sh -c {}", shell_words::quote(&script)))
+}
+
+fn get_value_db_client(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    if let Some(client_command) = try_prepare_db_client(item) {
+        Cow::Owned(client_command)
+    } else {
+        Cow::Borrowed(&item.item_text)
     }
 }
 
@@ -59,6 +444,16 @@ fn is_ssh_access(item: &ItemOfInterest) -> bool {
     }
 }
 
+fn is_ssh_tunnel_access(item: &ItemOfInterest) -> bool {
+    match &item.server_info {
+        Some(srv) => {
+            srv.server_access_type == ServerAccessType::SrvAccessSshTunnel
+                && !srv.jump_hosts.is_empty()
+        }
+        None => false,
+    }
+}
+
 #[derive(PartialEq)]
 enum ForcePseudoTTY {
     Yes,
@@ -131,6 +526,126 @@ fn get_value_fetch_file(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     }
 }
 
+// backup archive POIs are folders holding the backups,
+// most recent first when sorted by modification date
+fn get_value_list_backups(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    get_value_action_file(item, ForcePseudoTTY::No, Cow::Borrowed("ls -lt"))
+}
+
+// archive: the shell expression of the name of the archive to fetch
+fn fetch_backup_command(item: &ItemOfInterest, archive: &str) -> Option<String> {
+    let ssh_command = try_prepare_ssh_command(item, SshCommandType::Ssh)?;
+    let scp_command = try_prepare_ssh_command(item, SshCommandType::Scp)?;
+    let folder = item.poi_info.as_ref().unwrap().path.to_str().unwrap();
+    Some(format!(
+        "sh -c \"f={} && if [ \\$f = newest ]; then f=\\$({} 'ls -t {} | head -n 1'); fi && {}:{}/\\$f {}\"",
+        archive,
+        ssh_command,
+        folder,
+        scp_command,
+        folder,
+        dirs::download_dir().unwrap().to_str().unwrap()
+    ))
+}
+
+fn get_value_fetch_backup(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    match fetch_backup_command(item, "newest") {
+        Some(command) => Cow::Owned(command),
+        None => Cow::Borrowed(&item.item_text),
+    }
+}
+
+// ppcli asks for the name of the archive before running the command,
+// 'list bkp' shows the names
+fn get_value_fetch_chosen_backup(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    match fetch_backup_command(item, "{{ask:archive|default=newest}}") {
+        Some(command) => Cow::Owned(command),
+        None => Cow::Borrowed(&item.item_text),
+    }
+}
+
+fn get_value_verify_backup(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    if let Some(ssh_command) = try_prepare_ssh_command(item, SshCommandType::Ssh) {
+        // check that the newest archive can be read from start to end
+        Cow::Owned(format!(
+            "{} -t \"cd {} && f=\\$(ls -t | head -n 1) && echo \\$f && case \\$f in \
+             *.7z|*.zip) 7z t \\$f ;; \
+             *.tar.gz|*.tgz) tar -tzf \\$f > /dev/null && echo OK ;; \
+             *) tar -tf \\$f > /dev/null && echo OK ;; esac\"",
+            ssh_command,
+            item.poi_info.as_ref().unwrap().path.to_str().unwrap()
+        ))
+    } else {
+        Cow::Borrowed(&item.item_text)
+    }
+}
+
+fn current_dir_str() -> String {
+    std::env::current_dir()
+        .map(|d| d.to_string_lossy().to_string())
+        .unwrap_or_else(|_| ".".to_string())
+}
+
+// upload the file with the same name from the current folder, after
+// backing up the remote file. To upload another file, paste the command
+// to the prompt and edit it.
+fn get_value_upload_file(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    let remote_path = item.poi_info.as_ref().unwrap().path.to_str().unwrap();
+    let local_path = format!(
+        "{}/{}",
+        current_dir_str(),
+        item.poi_info
+            .as_ref()
+            .unwrap()
+            .path
+            .file_name()
+            .map(|f| f.to_string_lossy())
+            .unwrap_or_default()
+    );
+    let target = ssh_target(item.server_info.as_ref().unwrap());
+    match (
+        prepare_ssh_command(&target, SshCommandType::Ssh, &[]),
+        prepare_ssh_command(
+            &target,
+            SshCommandType::Scp,
+            &[shell_words::quote(&local_path).to_string()],
+        ),
+    ) {
+        (Some(ssh_command), Some(scp_command)) => Cow::Owned(format!(
+            "sh -c \"{} 'if [ -e {} ]; then cp -p {} {}.\\$(date +%Y%m%d-%H%M%S); fi' && {}:{}\"",
+            ssh_command, remote_path, remote_path, remote_path, scp_command, remote_path
+        )),
+        _ => Cow::Borrowed(&item.item_text),
+    }
+}
+
+// sync the current folder to the application folder, showing
+// what would change and asking for confirmation first
+fn get_value_sync_folder(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    let target = ssh_target(item.server_info.as_ref().unwrap());
+    match ssh_command_parts(&target, SshCommandType::Ssh) {
+        Some((ssh_params, destination)) => {
+            // rsync splits the -e value itself, honoring the quotes in it
+            let rsync_params = format!(
+                "-e {} {} {}",
+                shell_words::quote(&ssh_params.join(" ")),
+                shell_words::quote(&format!("{}/", current_dir_str())),
+                shell_words::quote(&format!(
+                    "{}:{}/",
+                    destination,
+                    item.poi_info.as_ref().unwrap().path.to_str().unwrap()
+                ))
+            );
+            let script = format!(
+                "rsync -azv --dry-run {} && printf 'Sync for real? [y/N] ' && read answer && [ x$answer = xy ] && rsync -azv --progress {}",
+                rsync_params, rsync_params
+            );
+            Cow::Owned(format!("sh -c {}", shell_words::quote(&script)))
+        }
+        None => Cow::Borrowed(&item.item_text),
+    }
+}
+
 // https://serverfault.com/a/738797/176574
 fn get_value_ssh_cd_in_folder(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     if let Some(ssh_command) = try_prepare_ssh_command(item, SshCommandType::Ssh) {
@@ -164,6 +679,21 @@ fn get_value_ssh_run_on_ssh(item: &ItemOfInterest) -> std::borrow::Cow<str> {
     Cow::Borrowed(&item.item_text)
 }
 
+fn get_value_open_url(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    Cow::Owned(format!(
+        "xdg-open {}",
+        shell_words::quote(&item.website_info.as_ref().unwrap().url)
+    ))
+}
+
+fn get_value_website_username(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    Cow::Borrowed(&item.website_info.as_ref().unwrap().username)
+}
+
+fn get_value_website_password(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    Cow::Borrowed(&item.website_info.as_ref().unwrap().password)
+}
+
 #[derive(PartialEq)]
 pub enum AllowedAction {
     Run,
@@ -171,33 +701,153 @@ pub enum AllowedAction {
     CopyToPrompt,
 }
 
+/// The secrets of the item that the command of the action needs
+/// while it runs, on disk or in its environment
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CommandSecrets {
+    None,
+    // the ssh keys of the server and of the jump hosts, and askpass
+    Ssh,
+    // also the password file for the database client
+    SshAndDatabase,
+}
+
 pub struct Action {
     pub item: ItemOfInterest,
-    pub desc: &'static str,
+    pub desc: Cow<'static, str>,
     pub get_string: fn(&ItemOfInterest) -> Cow<str>,
     pub allowed_actions: Vec<AllowedAction>,
+    // secrets are removed from the clipboard after a while
+    pub is_secret: bool,
+    pub secrets: CommandSecrets,
 }
 
 impl Action {
+    // most actions connect to the server through ssh
     fn new(
-        desc: &'static str,
+        desc: impl Into<Cow<'static, str>>,
         get_string: fn(&ItemOfInterest) -> Cow<str>,
         item: ItemOfInterest,
     ) -> Action {
         Action {
             item,
-            desc,
+            desc: desc.into(),
             get_string,
             allowed_actions: vec![
                 AllowedAction::Run,
                 AllowedAction::CopyToClipboard,
                 AllowedAction::CopyToPrompt,
             ],
+            is_secret: false,
+            secrets: CommandSecrets::Ssh,
         }
     }
+
+    // for commands which run locally
+    fn local(
+        desc: impl Into<Cow<'static, str>>,
+        get_string: fn(&ItemOfInterest) -> Cow<str>,
+        item: ItemOfInterest,
+    ) -> Action {
+        Action {
+            secrets: CommandSecrets::None,
+            ..Action::new(desc, get_string, item)
+        }
+    }
+
+    fn copy_value(
+        desc: &'static str,
+        get_string: fn(&ItemOfInterest) -> Cow<str>,
+        item: ItemOfInterest,
+        is_secret: bool,
+    ) -> Action {
+        Action {
+            item,
+            desc: Cow::Borrowed(desc),
+            get_string,
+            allowed_actions: vec![AllowedAction::CopyToClipboard],
+            is_secret,
+            secrets: CommandSecrets::None,
+        }
+    }
+}
+
+fn user_action_applies(user_action: &UserAction, item: &ItemOfInterest) -> bool {
+    user_action
+        .interest_type
+        .is_none_or(|t| item.item_type == ItemType::InterestItemType(t))
+        // the extra user accounts have the type of their server, but
+        // the action is for the server itself
+        && user_action.server_type.is_none_or(|t| {
+            item.sql_table == "server" && item.item_type == ItemType::ServerItemType(t)
+        })
+        && user_action.access_type.is_none_or(|t| {
+            item.server_info.as_ref().map(|si| si.server_access_type) == Some(t)
+        })
+        && (!user_action.runs_remotely || is_ssh_access(item) || is_ssh_tunnel_access(item))
 }
 
-pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
+fn expand_user_action_placeholders(template: &str, item: &ItemOfInterest) -> String {
+    let server_info = item.server_info.as_ref();
+    let (host, port) = server_info
+        .and_then(|si| split_host_port(&si.server_ip))
+        .unwrap_or(("", None));
+    template
+        .replace("{host}", host)
+        .replace("{port}", port.unwrap_or("22"))
+        .replace(
+            "{user}",
+            server_info.map_or("", |si| si.server_username.as_str()),
+        )
+        .replace(
+            "{path}",
+            &item
+                .poi_info
+                .as_ref()
+                .map(|p| p.path.to_string_lossy())
+                .unwrap_or_default(),
+        )
+        .replace("{text}", &item.item_text)
+}
+
+fn get_value_user_action(item: &ItemOfInterest) -> std::borrow::Cow<str> {
+    let user_action = item.user_action.as_ref().unwrap();
+    let command = expand_user_action_placeholders(&user_action.command, item);
+    if user_action.runs_remotely {
+        if let Some(ssh_command) = try_prepare_ssh_command(item, SshCommandType::Ssh) {
+            return Cow::Owned(format!(
+                "{} {}\"{}\"",
+                ssh_command,
+                if user_action.pseudo_tty { "-t " } else { "" },
+                command
+            ));
+        }
+    }
+    Cow::Owned(command)
+}
+
+pub fn get_value(item: ItemOfInterest, user_actions: &[UserAction]) -> Vec<Action> {
+    let user_defined: Vec<_> = user_actions
+        .iter()
+        .filter(|ua| user_action_applies(ua, &item))
+        .map(|ua| {
+            let item = ItemOfInterest {
+                user_action: Some(ua.clone()),
+                ..item.clone()
+            };
+            if ua.runs_remotely {
+                Action::new(ua.desc.clone(), get_value_user_action, item)
+            } else {
+                Action::local(ua.desc.clone(), get_value_user_action, item)
+            }
+        })
+        .collect();
+    let mut actions = get_builtin_actions(item);
+    actions.extend(user_defined);
+    actions
+}
+
+fn get_builtin_actions(item: ItemOfInterest) -> Vec<Action> {
     match &item {
         i if i.item_type == ItemType::InterestItemType(InterestType::PoiLogFile)
             && is_ssh_access(i) =>
@@ -208,25 +858,87 @@ pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
                 Action::new("fetch log", get_value_fetch_file, item),
             ]
         }
+        i if i.item_type == ItemType::InterestItemType(InterestType::PoiBackupArchive)
+            && is_ssh_access(i) =>
+        {
+            vec![
+                Action::new("list bkp", get_value_list_backups, item.clone()),
+                Action::new("fetch bkp", get_value_fetch_backup, item.clone()),
+                Action::new(
+                    "fetch chosen bkp",
+                    get_value_fetch_chosen_backup,
+                    item.clone(),
+                ),
+                Action::new("verify bkp", get_value_verify_backup, item),
+            ]
+        }
         i if i.item_type == ItemType::InterestItemType(InterestType::PoiApplication)
             && is_ssh_access(i) =>
         {
-            vec![Action::new("ssh folder", get_value_ssh_cd_in_folder, item)]
+            vec![
+                Action::new("ssh folder", get_value_ssh_cd_in_folder, item.clone()),
+                Action::new("sync folder", get_value_sync_folder, item),
+            ]
         }
         i if i.item_type == ItemType::InterestItemType(InterestType::PoiApplication)
             && i.server_info.is_none() =>
         {
             vec![Action {
-                desc: "go folder",
+                desc: Cow::Borrowed("go folder"),
                 get_string: get_value_cd_in_folder,
                 // cannot change the folder of the parent shell
                 allowed_actions: vec![AllowedAction::CopyToClipboard, AllowedAction::CopyToPrompt],
+                is_secret: false,
+                secrets: CommandSecrets::None,
                 item,
             }]
         }
         i if i.sql_table.as_str() == "server" && is_ssh_access(i) => {
             vec![Action::new("ssh shell", get_value_server_ssh, item)]
         }
+        i if i.sql_table.as_str() == "server_extra_user_account" && is_ssh_access(i) => {
+            let desc = format!("ssh as {}", i.item_text);
+            vec![Action::new(desc, get_value_server_ssh, item)]
+        }
+        i if i.item_type == ItemType::WebsiteItemType => {
+            let mut actions = vec![Action::local("open url", get_value_open_url, item.clone())];
+            let website_info = i.website_info.as_ref().unwrap();
+            if !website_info.username.is_empty() {
+                actions.push(Action::copy_value(
+                    "copy user",
+                    get_value_website_username,
+                    item.clone(),
+                    false,
+                ));
+            }
+            if !website_info.password.is_empty() {
+                actions.push(Action::copy_value(
+                    "copy pass",
+                    get_value_website_password,
+                    item,
+                    true,
+                ));
+            }
+            actions
+        }
+        i if i.sql_table.as_str() == "server" && is_ssh_tunnel_access(i) => {
+            vec![Action::new("forward port", get_value_forward_port, item)]
+        }
+        i if i.item_type == ItemType::DatabaseItemType
+            && i.db_info
+                .as_ref()
+                .and_then(|db| db.db_client.as_ref())
+                .is_some()
+            && (is_ssh_access(i) || is_ssh_tunnel_access(i)) =>
+        {
+            vec![
+                Action {
+                    secrets: CommandSecrets::SshAndDatabase,
+                    ..Action::new("open db", get_value_db_client, item.clone())
+                },
+                Action::new("forward db", get_value_forward_db, item),
+            ]
+        }
         i if [
             ItemType::InterestItemType(InterestType::PoiCommandToRun),
             ItemType::InterestItemType(InterestType::PoiCommandTerminal),
@@ -234,7 +946,7 @@ pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
         .contains(&i.item_type)
             && !is_ssh_access(i) =>
         {
-            vec![Action::new("run cmd", get_value_text, item)]
+            vec![Action::local("run cmd", get_value_text, item)]
         }
         i if [
             ItemType::InterestItemType(InterestType::PoiCommandToRun),
@@ -243,7 +955,11 @@ pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
         .contains(&i.item_type)
             && is_ssh_access(i) =>
         {
-            vec![Action::new("run cmd", get_value_ssh_run_on_ssh, item)]
+            if i.run_on == Some(RunOn::RunOnServer) {
+                vec![Action::new("run cmd", get_value_ssh_run_on_ssh, item)]
+            } else {
+                vec![Action::local("run cmd", get_value_ssh_run_on_ssh, item)]
+            }
         }
         i if i.item_type == ItemType::InterestItemType(InterestType::PoiConfigFile)
             && is_ssh_access(i) =>
@@ -251,7 +967,8 @@ pub fn get_value(item: ItemOfInterest) -> Vec<Action> {
             vec![
                 Action::new("edit cfg", get_value_edit_file, item.clone()),
                 Action::new("less cfg", get_value_less_file, item.clone()),
-                Action::new("fetch cfg", get_value_fetch_file, item),
+                Action::new("fetch cfg", get_value_fetch_file, item.clone()),
+                Action::new("upload cfg", get_value_upload_file, item),
             ]
         }
         _ => Vec::new(),
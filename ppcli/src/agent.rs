@@ -0,0 +1,187 @@
+// like ssh-agent: `ppcli agent` keeps the database key in memory, so that the
+// next ppcli invocations get it from a unix socket instead of going through the
+// keyring (or asking for the password). It holds the raw key, derived from the
+// password once, so the invocations also skip SQLCipher's slow key derivation.
+// It exits after some idle time, or on `ppcli lock`.
+// The socket is in the private folder which only the current user can access,
+// and on linux we also check the uid of the processes which connect to it.
+use crate::secret_files;
+use diesel::prelude::*;
+use hmac::Hmac;
+use sha1::Sha1;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const GET_KEY: &str = "GET";
+const LOCK: &str = "LOCK";
+
+// the key derivation parameters of projectpadsql::try_unlock_db (the sqlcipher3 defaults)
+const KDF_ITER: u32 = 64_000;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+
+struct AgentState {
+    db_key: String,
+    last_used: Instant,
+}
+
+fn socket_path() -> PathBuf {
+    secret_files::private_file_path("agent.sock")
+}
+
+/// The raw database key held by the agent, None if no agent is running.
+/// It unlocks the database like the password, but without the key derivation.
+pub fn get_db_key() -> Option<String> {
+    send_request(GET_KEY)
+        .ok()
+        .and_then(|response| response.strip_prefix("OK ").map(|p| p.to_string()))
+}
+
+pub fn lock() {
+    match send_request(LOCK) {
+        Ok(_) => eprintln!("The ppcli agent was stopped"),
+        Err(_) => eprintln!("No ppcli agent is running"),
+    }
+}
+
+fn send_request(request: &str) -> std::io::Result<String> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    stream.write_all(format!("{}\n", request).as_bytes())?;
+    // the agent closes the connection after the response
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+// SQLCipher derives the key from the password and the salt, which is at the
+// start of the database file. Given a raw key as x'<hex>', it uses it as is.
+fn derive_db_key(password: &str) -> Result<String, String> {
+    let db_path = projectpadsql::database_path();
+    let mut salt = [0; SALT_LEN];
+    File::open(&db_path)
+        .and_then(|mut f| f.read_exact(&mut salt))
+        .map_err(|e| format!("{}: {}", db_path.display(), e))?;
+    let mut key = [0; KEY_LEN];
+    pbkdf2::pbkdf2::<Hmac<Sha1>>(password.as_bytes(), &salt, KDF_ITER, &mut key);
+    let db_key = format!(
+        "x'{}'",
+        key.iter().map(|b| format!("{:02X}", b)).collect::<String>()
+    );
+    wipe(&mut key);
+    // make sure that the key unlocks the database before serving it
+    let conn = SqliteConnection::establish(&db_path.to_string_lossy())
+        .map_err(|e| format!("{}: {}", db_path.display(), e))?;
+    projectpadsql::try_unlock_db(&conn, &db_key)
+        .map_err(|e| format!("The derived key doesn't unlock the database: {}", e))?;
+    Ok(db_key)
+}
+
+/// Serve the database key until the agent wasn't used for idle_timeout, or until `ppcli lock`
+pub fn run(mut password: String, idle_timeout: Duration) -> Result<(), String> {
+    if get_db_key().is_some() {
+        return Err("A ppcli agent is already running".to_string());
+    }
+    let db_key = derive_db_key(&password);
+    wipe(unsafe { password.as_bytes_mut() });
+    let db_key = db_key?;
+    let path = socket_path();
+    secret_files::create_private_folder().map_err(|e| e.to_string())?;
+    // left over by an agent which was killed
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    eprintln!(
+        "ppcli agent running, it will stop after {} minutes without use, or with ppcli lock",
+        idle_timeout.as_secs() / 60
+    );
+
+    let state = Arc::new(Mutex::new(AgentState {
+        db_key,
+        last_used: Instant::now(),
+    }));
+    let timeout_state = state.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(10));
+        let mut state = timeout_state.lock().unwrap();
+        if state.last_used.elapsed() > idle_timeout {
+            stop(&mut state);
+        }
+    });
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        if !is_from_current_user(&stream) {
+            continue;
+        }
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+        let mut request = [0; 16];
+        let request = match stream.read(&mut request) {
+            Ok(read) => String::from_utf8_lossy(&request[..read]).trim().to_string(),
+            Err(_) => continue,
+        };
+        let mut state = state.lock().unwrap();
+        match request.as_str() {
+            GET_KEY => {
+                state.last_used = Instant::now();
+                let _ = stream.write_all(format!("OK {}", state.db_key).as_bytes());
+            }
+            LOCK => {
+                let _ = stream.write_all(b"OK");
+                stop(&mut state);
+            }
+            _ => {
+                let _ = stream.write_all(b"ERR unknown request");
+            }
+        }
+    }
+    Ok(())
+}
+
+// overwrite secrets when we're done with them, don't leave them in the freed memory
+fn wipe(bytes: &mut [u8]) {
+    for b in bytes {
+        unsafe { std::ptr::write_volatile(b, 0) };
+    }
+}
+
+fn stop(state: &mut AgentState) -> ! {
+    wipe(unsafe { state.db_key.as_bytes_mut() });
+    let _ = std::fs::remove_file(socket_path());
+    std::process::exit(0);
+}
+
+#[cfg(target_os = "linux")]
+fn is_from_current_user(stream: &UnixStream) -> bool {
+    use std::os::unix::io::AsRawFd;
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let res = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    res == 0 && cred.uid == unsafe { libc::getuid() }
+}
+
+// only the folder permissions protect the socket there
+#[cfg(not(target_os = "linux"))]
+fn is_from_current_user(_stream: &UnixStream) -> bool {
+    true
+}
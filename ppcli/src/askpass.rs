@@ -0,0 +1,86 @@
+// ppcli can act as a SSH_ASKPASS helper. When ppcli launches
+// a command for a server with a password, it points ssh to a small
+// wrapper script calling ppcli --askpass, and tells through an environment
+// variable which account we're logging in with.
+use crate::database;
+use crate::secret_files::{self, SecretFile};
+use diesel::prelude::*;
+use projectpadsql::models::ServerExtraUserAccount;
+use std::error::Error;
+use std::path::PathBuf;
+
+pub const ASKPASS_REF_ENV_VAR: &str = "PPCLI_ASKPASS_REF";
+
+pub fn askpass_script_path() -> PathBuf {
+    secret_files::run_file_path("askpass")
+}
+
+pub fn askpass_script() -> Option<SecretFile> {
+    let exe = std::env::current_exe().ok()?;
+    Some(SecretFile {
+        path: askpass_script_path(),
+        contents: format!(
+            "#!/bin/sh\nexec {} --askpass \"$1\"\n",
+            shell_words::quote(&exe.to_string_lossy())
+        )
+        .into_bytes(),
+        executable: true,
+    })
+}
+
+fn ip_host(ip: &str) -> &str {
+    ip.split(':').next().unwrap_or(ip)
+}
+
+pub fn get_password(
+    db_conn: &SqliteConnection,
+    askpass_ref: &str,
+    prompt: &str,
+) -> Result<String, Box<dyn Error>> {
+    // with SSH_ASKPASS_REQUIRE=force, ssh also asks its yes/no questions
+    // (for instance to confirm the host key) through us: answer only passwords
+    let lower_prompt = prompt.to_lowercase();
+    if lower_prompt.contains("passphrase") {
+        return Err("projectpad doesn't store passphrases for ssh keys".into());
+    }
+    if !lower_prompt.contains("password") {
+        return Err(format!("not a password prompt: {}", prompt).into());
+    }
+    let (table, id) = match askpass_ref.split(':').collect::<Vec<_>>()[..] {
+        [table, id] => (table, id.parse::<i32>()?),
+        _ => return Err(format!("invalid account reference: {}", askpass_ref).into()),
+    };
+    let servers_by_id = database::load_servers_by_id(db_conn);
+    let (username, password, server) = match table {
+        "server" => {
+            let server = servers_by_id.get(&id).ok_or("unknown server")?;
+            (server.username.clone(), server.password.clone(), server)
+        }
+        "server_extra_user_account" => {
+            use projectpadsql::schema::server_extra_user_account::dsl as srv_usr;
+            let user = srv_usr::server_extra_user_account
+                .filter(srv_usr::id.eq(id))
+                .first::<ServerExtraUserAccount>(db_conn)?;
+            let server = servers_by_id.get(&user.server_id).ok_or("unknown server")?;
+            (user.username, user.password, server)
+        }
+        _ => return Err(format!("invalid account reference: {}", askpass_ref).into()),
+    };
+    // the account on the target server first, then the servers we tunnel through
+    let mut candidates = vec![(username.as_str(), server.ip.as_str(), password.as_str())];
+    candidates.extend(
+        database::get_tunnel_chain(server, &servers_by_id)
+            .iter()
+            .map(|s| (s.username.as_str(), s.ip.as_str(), s.password.as_str())),
+    );
+    // ssh prompts look like "user@host's password: ", find out which server
+    // it's about. We don't answer other prompts.
+    let (_, _, password) = candidates
+        .iter()
+        .find(|(user, ip, _)| prompt.contains(&format!("{}@{}", user, ip_host(ip))))
+        .ok_or_else(|| format!("no stored account matches the prompt: {}", prompt))?;
+    if password.is_empty() {
+        return Err("no password is stored for that account".into());
+    }
+    Ok(password.to_string())
+}
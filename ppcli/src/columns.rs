@@ -0,0 +1,215 @@
+// the columns of the rows in the list. Which columns and in which order comes
+// from the configuration, their widths from the terminal width and the data.
+use crate::actions::Action;
+use crate::database::{self, DisplayMode};
+use serde_derive::Deserialize;
+use std::borrow::Cow;
+use std::fs::OpenOptions;
+use std::os::unix::io::AsRawFd;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// skim draws the cursor and the multi-selection marker before each row
+const SKIM_ROW_PREFIX_WIDTH: usize = 2;
+// we don't shrink columns below that when the terminal is narrow
+const MIN_SHRINK_WIDTH: usize = 6;
+const ELLIPSIS: char = '…';
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Project,
+    Env,
+    Type,
+    Server,
+    Ip,
+    Group,
+    Description,
+    Action,
+}
+
+pub fn default_columns() -> Vec<Column> {
+    vec![
+        Column::Project,
+        Column::Env,
+        Column::Type,
+        Column::Server,
+        Column::Description,
+        Column::Action,
+    ]
+}
+
+impl Column {
+    // even if the data is wider, so that one long entry doesn't take all the room
+    fn max_width(self) -> usize {
+        match self {
+            Column::Project => 25,
+            Column::Env => 4,
+            Column::Type => 3,
+            Column::Server => 30,
+            Column::Ip => 39,
+            Column::Group => 20,
+            Column::Description => 40,
+            Column::Action => 20,
+        }
+    }
+
+    // the short columns keep their width when the terminal is narrow
+    fn is_shrinkable(self) -> bool {
+        !matches!(self, Column::Env | Column::Type)
+    }
+}
+
+/// The contents of the cell, and its width on screen (the environment is colored)
+fn cell(column: Column, action: &Action, display_mode: DisplayMode) -> (Cow<str>, usize) {
+    let item = &action.item;
+    let text = match column {
+        Column::Project => Cow::Borrowed(item.project_name.as_str()),
+        Column::Env => {
+            return match &item.env {
+                Some(env) => (
+                    Cow::Borrowed(database::display_env(env, display_mode)),
+                    // the colored version has a ❚ prefix
+                    database::display_env(env, DisplayMode::Plain).len()
+                        + if display_mode == DisplayMode::Color {
+                            1
+                        } else {
+                            0
+                        },
+                ),
+                None => (Cow::Borrowed("-"), 1),
+            };
+        }
+        Column::Type => Cow::Borrowed(database::render_type(&item.item_type)),
+        Column::Server => Cow::Borrowed(
+            item.server_info
+                .as_ref()
+                .map(|si| si.server_desc.as_str())
+                .unwrap_or("-"),
+        ),
+        Column::Ip => Cow::Borrowed(
+            item.server_info
+                .as_ref()
+                .map(|si| si.server_ip.as_str())
+                .unwrap_or("-"),
+        ),
+        Column::Group => Cow::Borrowed(item.group_name.as_deref().unwrap_or("-")),
+        Column::Description => Cow::Borrowed(item.poi_desc.as_deref().unwrap_or("")),
+        Column::Action => Cow::Borrowed(&*action.desc),
+    };
+    let width = text.width();
+    (text, width)
+}
+
+/// The width of each column: wide enough for the data if possible, and the
+/// widest columns get shrunk until the rows fit in the terminal.
+pub fn compute_widths(
+    columns: &[Column],
+    actions: &[Action],
+    display_mode: DisplayMode,
+    extra_width: usize,
+) -> Vec<usize> {
+    let mut widths: Vec<_> = columns
+        .iter()
+        .map(|col| {
+            actions
+                .iter()
+                .map(|action| cell(*col, action, display_mode).1)
+                .max()
+                .unwrap_or(0)
+                .min(col.max_width())
+        })
+        .collect();
+    let available = terminal_width().saturating_sub(SKIM_ROW_PREFIX_WIDTH + extra_width);
+    // one space between columns
+    let separators = columns.len().saturating_sub(1);
+    while widths.iter().sum::<usize>() + separators > available {
+        let widest = (0..columns.len())
+            .filter(|i| columns[*i].is_shrinkable() && widths[*i] > MIN_SHRINK_WIDTH)
+            .max_by_key(|i| widths[*i]);
+        match widest {
+            Some(i) => widths[i] -= 1,
+            None => break,
+        }
+    }
+    widths
+}
+
+pub fn render_row(
+    columns: &[Column],
+    widths: &[usize],
+    action: &Action,
+    display_mode: DisplayMode,
+) -> String {
+    columns
+        .iter()
+        .zip(widths)
+        .map(|(col, width)| {
+            let (text, text_width) = cell(*col, action, display_mode);
+            if text_width > *width {
+                // the environment is never shrunk, we don't truncate colored text
+                truncate(&text, *width).into_owned()
+            } else {
+                format!("{}{}", text, " ".repeat(width - text_width))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Cut the text to fit in that width, on a grapheme boundary, with an ellipsis
+fn truncate(text: &str, width: usize) -> Cow<str> {
+    if text.width() <= width {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::new();
+    let mut result_width = 0;
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme.width();
+        // keep room for the ellipsis
+        if result_width + grapheme_width + 1 > width {
+            break;
+        }
+        result.push_str(grapheme);
+        result_width += grapheme_width;
+    }
+    if width > 0 {
+        result.push(ELLIPSIS);
+        result_width += 1;
+    }
+    // a wide character may not fit exactly
+    result.push_str(&" ".repeat(width - result_width));
+    Cow::Owned(result)
+}
+
+fn terminal_width() -> usize {
+    // our stdout may be captured by the shell integration, ask the terminal itself
+    let tty_width = OpenOptions::new()
+        .read(true)
+        .open("/dev/tty")
+        .ok()
+        .and_then(|tty| {
+            let mut winsize = unsafe { std::mem::zeroed::<libc::winsize>() };
+            if unsafe { libc::ioctl(tty.as_raw_fd(), libc::TIOCGWINSZ, &mut winsize) } == 0
+                && winsize.ws_col > 0
+            {
+                Some(winsize.ws_col as usize)
+            } else {
+                None
+            }
+        });
+    tty_width
+        .or_else(|| std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()))
+        .unwrap_or(120)
+}
+
+#[test]
+fn truncate_on_grapheme_boundaries() {
+    assert_eq!("billing", truncate("billing", 7));
+    assert_eq!("Ärzte…", truncate("Ärzte-Verwaltung", 6));
+    // e + combining acute accent: one grapheme
+    assert_eq!("cafe\u{301}…", truncate("cafe\u{301} du commerce", 5));
+    // double-width characters
+    assert_eq!("日本… ", truncate("日本語のプロジェクト", 6));
+    assert_eq!("", truncate("billing", 0));
+}
@@ -1,10 +1,160 @@
 // bits lifted from the skim project
+use crate::actions::Action;
+use crate::columns::{self, Column};
+use crate::database::{self, DatabaseKind};
+use projectpadsql::models::{InterestType, ServerAccessType, ServerType};
+use serde_derive::Deserialize;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{BufReader, BufWriter};
 use std::path::PathBuf;
 use std::time::{Duration, SystemTime};
 
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Config {
+    // how to find out the database kind & which client to use for it.
+    // The first entry with a keyword matching the database wins.
+    pub database_clients: Vec<DatabaseClient>,
+    // when false, we connect to the databases through the ssh
+    // server they're on, otherwise we connect to them directly
+    pub databases_direct_access: bool,
+    // passwords copied to the clipboard are removed from it
+    // after that many seconds. 0 to keep them.
+    pub clipboard_clear_seconds: u64,
+    // per environment (dev, stg, uat, prd), the actions (for instance
+    // "run cmd") to confirm before running them. "*" for all actions.
+    pub confirm_actions: HashMap<String, Vec<String>>,
+    // extra actions, on top of the ones built in ppcli
+    pub actions: Vec<UserAction>,
+    // a command printing the database password, for instance "pass show projectpad",
+    // used when there is no OS keyring
+    pub password_command: Option<String>,
+    // the columns of the list, in that order
+    pub columns: Vec<Column>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            database_clients: default_database_clients(),
+            databases_direct_access: false,
+            clipboard_clear_seconds: 30,
+            confirm_actions: vec![(
+                "prd".to_string(),
+                vec![
+                    "run cmd".to_string(),
+                    "edit cfg".to_string(),
+                    "upload cfg".to_string(),
+                    "sync folder".to_string(),
+                ],
+            )]
+            .into_iter()
+            .collect(),
+            actions: vec![],
+            password_command: None,
+            columns: columns::default_columns(),
+        }
+    }
+}
+
+impl Config {
+    pub fn needs_confirmation(&self, action: &Action) -> bool {
+        let item_env = match action.item.env {
+            Some(env) => env,
+            None => return false,
+        };
+        self.confirm_actions
+            .iter()
+            .filter(|(env, _)| database::parse_env(env) == Ok(item_env))
+            .flat_map(|(_, actions)| actions)
+            .any(|a| a == "*" || a.eq_ignore_ascii_case(&action.desc))
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct UserAction {
+    // displayed in the action column
+    pub desc: String,
+    // the placeholders {host}, {port}, {user}, {path} and {text}
+    // are replaced by the values of the item
+    pub command: String,
+    // the action is offered for the items matching all the criteria
+    // which are given (for instance PoiLogFile, SrvDatabase, SrvAccessSsh)
+    pub interest_type: Option<InterestType>,
+    pub server_type: Option<ServerType>,
+    pub access_type: Option<ServerAccessType>,
+    // run the command on the server through ssh, otherwise locally
+    #[serde(default = "default_runs_remotely")]
+    pub runs_remotely: bool,
+    // force the allocation of a pseudo-terminal by ssh, for interactive commands
+    #[serde(default)]
+    pub pseudo_tty: bool,
+}
+
+fn default_runs_remotely() -> bool {
+    true
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DatabaseClient {
+    // matched (ignoring case) against the description and text of the database
+    pub keyword: String,
+    pub kind: DatabaseKind,
+    // the client program to run, by default psql, mysql or sqlcmd
+    pub command: Option<String>,
+    pub port: Option<i32>,
+}
+
+impl DatabaseClient {
+    pub fn command(&self) -> &str {
+        self.command
+            .as_deref()
+            .unwrap_or_else(|| self.kind.default_command())
+    }
+
+    pub fn port(&self) -> i32 {
+        self.port.unwrap_or_else(|| self.kind.default_port())
+    }
+}
+
+fn default_database_clients() -> Vec<DatabaseClient> {
+    [
+        ("postgres", DatabaseKind::PostgreSql),
+        ("psql", DatabaseKind::PostgreSql),
+        ("mysql", DatabaseKind::MySql),
+        ("mariadb", DatabaseKind::MySql),
+        ("sql server", DatabaseKind::SqlServer),
+        ("sqlserver", DatabaseKind::SqlServer),
+        ("mssql", DatabaseKind::SqlServer),
+    ]
+    .iter()
+    .map(|(keyword, kind)| DatabaseClient {
+        keyword: keyword.to_string(),
+        kind: *kind,
+        command: None,
+        port: None,
+    })
+    .collect()
+}
+
+fn config_file_path() -> PathBuf {
+    let mut path = projectpadsql::config_path();
+    path.push("ppcli.toml");
+    path
+}
+
+pub fn read_config() -> Result<Config, Box<dyn std::error::Error>> {
+    let file_path = config_file_path();
+    if !file_path.is_file() {
+        return Ok(Config::default());
+    }
+    let mut contents = String::new();
+    File::open(&file_path)?.read_to_string(&mut contents)?;
+    toml::from_str(&contents).map_err(|e| format!("{}: {}", file_path.display(), e).into())
+}
+
 fn upgrade_check_time_path() -> PathBuf {
     let mut path = projectpadsql::config_path();
     path.push("upgrade-check-date");
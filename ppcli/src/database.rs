@@ -1,8 +1,12 @@
 use super::actions;
+use super::columns;
+use crate::config::{Config, DatabaseClient, UserAction};
 use diesel::prelude::*;
 use diesel::sqlite::SqliteConnection;
 use projectpadsql::models::*;
+use serde_derive::Deserialize;
 use skim::prelude::*;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
 #[derive(Debug, PartialEq, Clone, PartialOrd, Ord, Eq)]
@@ -11,6 +15,16 @@ pub enum ItemType {
     // type for sorting of the display
     ServerItemType(ServerType),
     InterestItemType(InterestType),
+    DatabaseItemType,
+    WebsiteItemType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AuthKey {
+    // the name of the file under which we'll write the key
+    // on disk when running commands
+    pub file_name: String,
+    pub contents: Vec<u8>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -19,6 +33,124 @@ pub struct ServerInfo {
     pub server_username: String,
     pub server_ip: String,
     pub server_access_type: ServerAccessType,
+    pub server_auth_key: Option<AuthKey>,
+    // the servers we must go through to reach this server,
+    // starting with the one we connect to first
+    pub jump_hosts: Vec<JumpHost>,
+    // for SSH tunnel servers, the local port to forward
+    pub ssh_tunnel_port: Option<i32>,
+    // for the askpass helper: the account whose password we log in with,
+    // for instance "server:12". None when no password is needed on the way.
+    pub askpass_ref: Option<String>,
+    // the first server notes, filled in by load_items
+    pub server_notes: String,
+    pub server_retired: bool,
+    pub server_id: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct JumpHost {
+    pub server_username: String,
+    pub server_ip: String,
+    pub server_auth_key: Option<AuthKey>,
+}
+
+fn server_auth_key(server: &Server) -> Option<AuthKey> {
+    server.auth_key.as_ref().map(|contents| AuthKey {
+        file_name: format!("server-{}.key", server.id),
+        contents: contents.clone(),
+    })
+}
+
+impl ServerInfo {
+    fn from_server(server: Server, servers_by_id: &HashMap<i32, Server>) -> ServerInfo {
+        let tunnel_chain = get_tunnel_chain(&server, servers_by_id);
+        ServerInfo {
+            askpass_ref: askpass_ref(
+                format!("server:{}", server.id),
+                &server.password,
+                &tunnel_chain,
+            ),
+            jump_hosts: get_jump_hosts(&tunnel_chain),
+            server_auth_key: server_auth_key(&server),
+            ssh_tunnel_port: server.ssh_tunnel_port,
+            server_desc: server.desc,
+            server_username: server.username,
+            server_ip: server.ip,
+            server_access_type: server.access_type,
+            server_notes: String::new(),
+            server_retired: server.is_retired,
+            server_id: server.id,
+        }
+    }
+}
+
+fn askpass_ref(account_ref: String, password: &str, tunnel_chain: &[&Server]) -> Option<String> {
+    Some(account_ref)
+        .filter(|_| !password.is_empty() || tunnel_chain.iter().any(|s| !s.password.is_empty()))
+}
+
+/// The servers we tunnel through to reach the server, starting
+/// from the one closest to the server.
+pub fn get_tunnel_chain<'a>(
+    server: &Server,
+    servers_by_id: &'a HashMap<i32, Server>,
+) -> Vec<&'a Server> {
+    let mut chain = vec![];
+    let mut visited_ids = HashSet::new();
+    visited_ids.insert(server.id);
+    let mut cur_tunnel_id = server.ssh_tunnel_through_server_id;
+    while let Some(tunnel_server) = cur_tunnel_id.and_then(|id| servers_by_id.get(&id)) {
+        if !visited_ids.insert(tunnel_server.id) {
+            // a loop in the tunnel chain, give up on the rest of the chain
+            break;
+        }
+        chain.push(tunnel_server);
+        cur_tunnel_id = tunnel_server.ssh_tunnel_through_server_id;
+    }
+    chain
+}
+
+fn get_jump_hosts(tunnel_chain: &[&Server]) -> Vec<JumpHost> {
+    // the chain starts from the target server,
+    // but ssh wants the hops starting from the first one
+    tunnel_chain
+        .iter()
+        .rev()
+        .map(|tunnel_server| JumpHost {
+            server_username: tunnel_server.username.clone(),
+            server_ip: tunnel_server.ip.clone(),
+            server_auth_key: server_auth_key(tunnel_server),
+        })
+        .collect()
+}
+
+pub fn load_servers_by_id(db_conn: &SqliteConnection) -> HashMap<i32, Server> {
+    use projectpadsql::schema::server::dsl as srv;
+    srv::server
+        .load::<Server>(db_conn)
+        .unwrap()
+        .into_iter()
+        .map(|s| (s.id, s))
+        .collect()
+}
+
+/// The notes of each server, by server id, with the title of each note as a heading
+fn load_server_notes(db_conn: &SqliteConnection) -> HashMap<i32, String> {
+    use projectpadsql::schema::server_note::dsl as srv_note;
+    let mut notes_by_server: HashMap<i32, String> = HashMap::new();
+    for note in srv_note::server_note
+        .order((srv_note::server_id.asc(), srv_note::title.asc()))
+        .load::<ServerNote>(db_conn)
+        .unwrap()
+    {
+        let notes = notes_by_server.entry(note.server_id).or_default();
+        if !notes.is_empty() {
+            notes.push('\n');
+        }
+        notes.push_str(&format!("# {}\n{}", note.title, note.contents));
+    }
+    notes_by_server
 }
 
 #[derive(Debug, Clone)]
@@ -26,72 +158,113 @@ pub struct PoiInfo {
     pub path: PathBuf,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseKind {
+    PostgreSql,
+    MySql,
+    SqlServer,
+}
+
+impl DatabaseKind {
+    pub fn default_port(self) -> i32 {
+        match self {
+            DatabaseKind::PostgreSql => 5432,
+            DatabaseKind::MySql => 3306,
+            DatabaseKind::SqlServer => 1433,
+        }
+    }
+
+    pub fn default_command(self) -> &'static str {
+        match self {
+            DatabaseKind::PostgreSql => "psql",
+            DatabaseKind::MySql => "mysql",
+            DatabaseKind::SqlServer => "sqlcmd",
+        }
+    }
+}
+
+// projectpad doesn't store the database type, guess it
+// from the description and the free text of the database
+fn find_database_client(db: &ServerDatabase, config: &Config) -> Option<DatabaseClient> {
+    let haystack = format!("{} {}", db.desc, db.text).to_lowercase();
+    config
+        .database_clients
+        .iter()
+        .find(|c| haystack.contains(&c.keyword.to_lowercase()))
+        .cloned()
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseInfo {
+    pub db_name: String,
+    pub db_username: String,
+    pub db_password: String,
+    pub db_client: Option<DatabaseClient>,
+    pub db_direct_access: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebsiteInfo {
+    pub url: String,
+    pub username: String,
+    pub password: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct ItemOfInterest {
     pub id: i32,
     pub sql_table: String,
+    pub project_id: i32,
     pub project_name: String,
+    // the group of the item, or else of its server
+    pub group_name: Option<String>,
     pub env: Option<EnvironmentType>,
     pub item_type: ItemType,
     pub poi_desc: Option<String>,
     pub item_text: String,
     pub server_info: Option<ServerInfo>,
     pub poi_info: Option<PoiInfo>,
+    pub db_info: Option<DatabaseInfo>,
+    pub website_info: Option<WebsiteInfo>,
     pub run_on: Option<RunOn>,
+    // for the actions defined in the configuration file
+    pub user_action: Option<UserAction>,
 }
 
-fn filter_servers(db_conn: &SqliteConnection) -> Vec<ItemOfInterest> {
+fn filter_servers(
+    db_conn: &SqliteConnection,
+    servers_by_id: &HashMap<i32, Server>,
+) -> Vec<ItemOfInterest> {
     use projectpadsql::schema::project::dsl as prj;
     use projectpadsql::schema::server::dsl as srv;
     srv::server
         .inner_join(prj::project)
-        .select((
-            srv::id,
-            prj::name,
-            srv::desc,
-            srv::environment,
-            srv::ip,
-            srv::server_type,
-            srv::username,
-            srv::access_type,
-        ))
+        .select((prj::name, projectpadsql::schema::server::all_columns))
         .filter(srv::access_type.ne_all(vec![
             ServerAccessType::SrvAccessRdp,
             ServerAccessType::SrvAccessWww,
         ]))
-        .load::<(_, _, String, _, String, _, _, _)>(db_conn)
+        .load::<(String, Server)>(db_conn)
         .unwrap()
         .into_iter()
-        .map(
-            |(
-                id,
-                project_name,
-                server_desc,
-                srv_env,
-                server_ip,
-                server_type,
-                server_username,
-                server_access_type,
-            )| {
-                ItemOfInterest {
-                    id,
-                    sql_table: "server".to_string(),
-                    project_name,
-                    env: Some(srv_env),
-                    item_type: ItemType::ServerItemType(server_type),
-                    poi_desc: Some(server_desc.clone()),
-                    item_text: server_ip.clone(),
-                    server_info: Some(ServerInfo {
-                        server_desc,
-                        server_username,
-                        server_ip,
-                        server_access_type,
-                    }),
-                    poi_info: None,
-                    run_on: None,
-                }
-            },
-        )
+        .map(|(project_name, server)| ItemOfInterest {
+            id: server.id,
+            sql_table: "server".to_string(),
+            group_name: server.group_name.clone(),
+            project_id: server.project_id,
+            project_name,
+            env: Some(server.environment),
+            item_type: ItemType::ServerItemType(server.server_type),
+            poi_desc: Some(server.desc.clone()),
+            item_text: server.ip.clone(),
+            server_info: Some(ServerInfo::from_server(server, servers_by_id)),
+            poi_info: None,
+            db_info: None,
+            website_info: None,
+            run_on: None,
+            user_action: None,
+        })
         .collect()
 }
 
@@ -107,15 +280,28 @@ fn filter_project_pois(db_conn: &SqliteConnection) -> Vec<ItemOfInterest> {
             prj_poi::text,
             prj_poi::interest_type,
             prj_poi::path,
+            prj_poi::project_id,
+            prj_poi::group_name,
         ))
-        .load::<(_, _, _, _, _, String)>(db_conn)
+        .load::<(_, _, _, _, _, String, _, _)>(db_conn)
         .unwrap()
         .into_iter()
         .map(
-            |(id, project_name, prj_poi_desc, item_text, prj_poi_interest_type, prj_path)| {
+            |(
+                id,
+                project_name,
+                prj_poi_desc,
+                item_text,
+                prj_poi_interest_type,
+                prj_path,
+                project_id,
+                group_name,
+            )| {
                 ItemOfInterest {
                     id,
                     sql_table: "project_point_of_interest".to_string(),
+                    group_name,
+                    project_id,
                     project_name,
                     env: None,
                     item_type: ItemType::InterestItemType(prj_poi_interest_type),
@@ -125,14 +311,20 @@ fn filter_project_pois(db_conn: &SqliteConnection) -> Vec<ItemOfInterest> {
                     poi_info: Some(PoiInfo {
                         path: prj_path.into(),
                     }),
+                    db_info: None,
+                    website_info: None,
                     run_on: None,
+                    user_action: None,
                 }
             },
         )
         .collect()
 }
 
-fn filter_server_pois(db_conn: &SqliteConnection) -> Vec<ItemOfInterest> {
+fn filter_server_pois(
+    db_conn: &SqliteConnection,
+    servers_by_id: &HashMap<i32, Server>,
+) -> Vec<ItemOfInterest> {
     use projectpadsql::schema::project::dsl as prj;
     use projectpadsql::schema::server::dsl as srv;
     use projectpadsql::schema::server_point_of_interest::dsl as srv_poi;
@@ -141,74 +333,298 @@ fn filter_server_pois(db_conn: &SqliteConnection) -> Vec<ItemOfInterest> {
         .select((
             srv_poi::id,
             prj::name,
-            srv::desc,
             srv_poi::desc,
-            srv::environment,
             srv_poi::text,
             srv_poi::interest_type,
-            srv::username,
             srv_poi::path,
-            srv::access_type,
-            srv::ip,
             srv_poi::run_on,
+            srv_poi::group_name,
+            projectpadsql::schema::server::all_columns,
         ))
         .filter(srv::access_type.ne_all(vec![
             ServerAccessType::SrvAccessRdp,
             ServerAccessType::SrvAccessWww,
         ]))
-        .load::<(_, _, _, _, _, _, _, _, String, _, _, _)>(db_conn)
+        .load::<(_, _, _, _, _, String, _, Option<String>, Server)>(db_conn)
         .unwrap()
         .into_iter()
         .map(
             |(
                 id,
                 project_name,
-                server_desc,
                 server_poi_desc,
-                srv_env,
                 item_text,
                 srv_poi_interest_type,
-                server_username,
                 srv_poi_path,
-                server_access_type,
-                server_ip,
                 run_on_val,
+                group_name,
+                server,
             )| {
                 ItemOfInterest {
                     id,
                     sql_table: "server_point_of_interest".to_string(),
+                    group_name: group_name.or_else(|| server.group_name.clone()),
+                    project_id: server.project_id,
                     project_name,
-                    env: Some(srv_env),
+                    env: Some(server.environment),
                     item_type: ItemType::InterestItemType(srv_poi_interest_type),
                     poi_desc: Some(server_poi_desc),
                     item_text,
-                    server_info: Some(ServerInfo {
-                        server_desc,
-                        server_username,
-                        server_ip,
-                        server_access_type,
-                    }),
+                    server_info: Some(ServerInfo::from_server(server, servers_by_id)),
                     poi_info: Some(PoiInfo {
                         path: srv_poi_path.into(),
                     }),
+                    db_info: None,
+                    website_info: None,
                     run_on: Some(run_on_val),
+                    user_action: None,
                 }
             },
         )
         .collect()
 }
 
+fn filter_server_databases(
+    db_conn: &SqliteConnection,
+    servers_by_id: &HashMap<i32, Server>,
+    config: &Config,
+) -> Vec<ItemOfInterest> {
+    use projectpadsql::schema::project::dsl as prj;
+    use projectpadsql::schema::server::dsl as srv;
+    use projectpadsql::schema::server_database::dsl as srv_db;
+    srv_db::server_database
+        .inner_join(srv::server.inner_join(prj::project))
+        .select((
+            prj::name,
+            projectpadsql::schema::server_database::all_columns,
+            projectpadsql::schema::server::all_columns,
+        ))
+        .filter(srv::access_type.ne_all(vec![
+            ServerAccessType::SrvAccessRdp,
+            ServerAccessType::SrvAccessWww,
+        ]))
+        .load::<(String, ServerDatabase, Server)>(db_conn)
+        .unwrap()
+        .into_iter()
+        .map(|(project_name, db, server)| ItemOfInterest {
+            id: db.id,
+            sql_table: "server_database".to_string(),
+            group_name: db.group_name.clone().or_else(|| server.group_name.clone()),
+            project_id: server.project_id,
+            project_name,
+            env: Some(server.environment),
+            item_type: ItemType::DatabaseItemType,
+            poi_desc: Some(db.desc.clone()),
+            item_text: db.name.clone(),
+            server_info: Some(ServerInfo::from_server(server, servers_by_id)),
+            poi_info: None,
+            db_info: Some(DatabaseInfo {
+                db_client: find_database_client(&db, config),
+                db_direct_access: config.databases_direct_access,
+                db_name: db.name,
+                db_username: db.username,
+                db_password: db.password,
+            }),
+            website_info: None,
+            run_on: None,
+            user_action: None,
+        })
+        .collect()
+}
+
+fn filter_server_websites(
+    db_conn: &SqliteConnection,
+    servers_by_id: &HashMap<i32, Server>,
+) -> Vec<ItemOfInterest> {
+    use projectpadsql::schema::project::dsl as prj;
+    use projectpadsql::schema::server::dsl as srv;
+    use projectpadsql::schema::server_website::dsl as srv_www;
+    // no filter on the server access type: websites are relevant
+    // even on servers that we can't access through ssh
+    srv_www::server_website
+        .inner_join(srv::server.inner_join(prj::project))
+        .select((
+            prj::name,
+            projectpadsql::schema::server_website::all_columns,
+            projectpadsql::schema::server::all_columns,
+        ))
+        .load::<(String, ServerWebsite, Server)>(db_conn)
+        .unwrap()
+        .into_iter()
+        .map(|(project_name, www, server)| ItemOfInterest {
+            id: www.id,
+            sql_table: "server_website".to_string(),
+            group_name: www.group_name.clone().or_else(|| server.group_name.clone()),
+            project_id: server.project_id,
+            project_name,
+            env: Some(server.environment),
+            item_type: ItemType::WebsiteItemType,
+            poi_desc: Some(www.desc.clone()),
+            item_text: www.url.clone(),
+            server_info: Some(ServerInfo::from_server(server, servers_by_id)),
+            poi_info: None,
+            db_info: None,
+            website_info: Some(WebsiteInfo {
+                url: www.url,
+                username: www.username,
+                password: www.password,
+            }),
+            run_on: None,
+            user_action: None,
+        })
+        .collect()
+}
+
+fn filter_server_extra_users(
+    db_conn: &SqliteConnection,
+    servers_by_id: &HashMap<i32, Server>,
+) -> Vec<ItemOfInterest> {
+    use projectpadsql::schema::project::dsl as prj;
+    use projectpadsql::schema::server::dsl as srv;
+    use projectpadsql::schema::server_extra_user_account::dsl as srv_usr;
+    srv_usr::server_extra_user_account
+        .inner_join(srv::server.inner_join(prj::project))
+        .select((
+            prj::name,
+            projectpadsql::schema::server_extra_user_account::all_columns,
+            projectpadsql::schema::server::all_columns,
+        ))
+        .filter(srv::access_type.ne_all(vec![
+            ServerAccessType::SrvAccessRdp,
+            ServerAccessType::SrvAccessWww,
+        ]))
+        .load::<(String, ServerExtraUserAccount, Server)>(db_conn)
+        .unwrap()
+        .into_iter()
+        .map(|(project_name, user, server)| {
+            let server_type = server.server_type;
+            let env = server.environment;
+            let project_id = server.project_id;
+            let group_name = user
+                .group_name
+                .clone()
+                .or_else(|| server.group_name.clone());
+            let user_id = user.id;
+            let user_askpass_ref = askpass_ref(
+                format!("server_extra_user_account:{}", user_id),
+                &user.password,
+                &get_tunnel_chain(&server, servers_by_id),
+            );
+            // same server, but we log in with the account's username and key
+            let server_info = ServerInfo {
+                askpass_ref: user_askpass_ref,
+                server_username: user.username.clone(),
+                server_auth_key: user.auth_key.map(|contents| AuthKey {
+                    file_name: format!("extra-user-{}.key", user_id),
+                    contents,
+                }),
+                ..ServerInfo::from_server(server, servers_by_id)
+            };
+            ItemOfInterest {
+                id: user_id,
+                sql_table: "server_extra_user_account".to_string(),
+                group_name,
+                project_id,
+                project_name,
+                env: Some(env),
+                item_type: ItemType::ServerItemType(server_type),
+                poi_desc: Some(if user.desc.is_empty() {
+                    user.username.clone()
+                } else {
+                    user.desc
+                }),
+                item_text: user.username,
+                server_info: Some(server_info),
+                poi_info: None,
+                db_info: None,
+                website_info: None,
+                run_on: None,
+                user_action: None,
+            }
+        })
+        .collect()
+}
+
+/// Restricts the items that we load, from the command-line options
+pub struct ItemsFilter {
+    pub project: Option<String>,
+    pub env: Option<EnvironmentType>,
+    pub item_type: Option<String>,
+    pub include_retired: bool,
+}
+
+impl ItemsFilter {
+    fn matches(&self, item: &ItemOfInterest) -> bool {
+        (self.include_retired || !is_retired(item))
+            && self
+                .project
+                .as_ref()
+                .is_none_or(|p| item.project_name.to_lowercase().contains(&p.to_lowercase()))
+            && self.env.is_none_or(|e| item.env == Some(e))
+            && self
+                .item_type
+                .as_ref()
+                .is_none_or(|t| render_type(&item.item_type) == t)
+    }
+}
+
+pub fn is_retired(item: &ItemOfInterest) -> bool {
+    item.server_info
+        .as_ref()
+        .is_some_and(|si| si.server_retired)
+}
+
+pub fn parse_env(env: &str) -> Result<EnvironmentType, String> {
+    match env.to_lowercase().as_str() {
+        "dev" | "development" => Ok(EnvironmentType::EnvDevelopment),
+        "stg" | "stage" | "staging" => Ok(EnvironmentType::EnvStage),
+        "uat" => Ok(EnvironmentType::EnvUat),
+        "prd" | "prod" | "production" => Ok(EnvironmentType::EnvProd),
+        _ => Err(format!(
+            "unknown environment {}, expected dev, stg, uat or prd",
+            env
+        )),
+    }
+}
+
+const ITEM_TYPES: &[&str] = &[
+    "CMD", "CFG", "LOG", "APP", "BKP", "SRA", "DAT", "HTT", "REP", "MON", "DB", "WWW",
+];
+
+pub fn parse_item_type(item_type: &str) -> Result<String, String> {
+    let item_type = item_type.to_uppercase();
+    if ITEM_TYPES.contains(&item_type.as_str()) {
+        Ok(item_type)
+    } else {
+        Err(format!(
+            "unknown type {}, expected one of {}",
+            item_type,
+            ITEM_TYPES.join(", ")
+        ))
+    }
+}
+
 pub fn load_items(
     conn: &SqliteConnection,
-    display_mode: DisplayMode,
-    item_sender: &Sender<Arc<dyn SkimItem>>,
-) {
-    let mut items = filter_server_pois(&conn);
-    items.extend(filter_project_pois(&conn));
-    items.extend(filter_servers(&conn));
-    if items.is_empty() {
-        println!("No items to display. Keep in mind that ppcli will only display non RDP/non WWW servers, and point of interests");
-        std::process::exit(0);
+    config: &Config,
+    filter: &ItemsFilter,
+) -> Vec<ItemOfInterest> {
+    let servers_by_id = load_servers_by_id(conn);
+    let mut items = filter_server_pois(conn, &servers_by_id);
+    items.extend(filter_project_pois(conn));
+    items.extend(filter_servers(conn, &servers_by_id));
+    items.extend(filter_server_databases(conn, &servers_by_id, config));
+    items.extend(filter_server_websites(conn, &servers_by_id));
+    items.extend(filter_server_extra_users(conn, &servers_by_id));
+    items.retain(|item| filter.matches(item));
+    let server_notes = load_server_notes(conn);
+    for si in items
+        .iter_mut()
+        .filter_map(|item| item.server_info.as_mut())
+    {
+        if let Some(notes) = server_notes.get(&si.server_id) {
+            si.server_notes = notes.clone();
+        }
     }
     items.sort_by(|a, b| {
         b.project_name
@@ -217,61 +633,76 @@ pub fn load_items(
             .then(b.item_type.cmp(&a.item_type))
             .then(b.item_text.cmp(&a.item_text))
     });
-    let cols_spec = vec![7, 3, 4, 30, 25, 10];
-    for action in items.into_iter().flat_map(actions::get_value) {
+    items
+}
+
+/// frecency_scores: by action reference, the actions used often and recently
+/// come first. Empty to keep the default order.
+pub fn send_items(
+    items: Vec<ItemOfInterest>,
+    config: &Config,
+    frecency_scores: &HashMap<String, u64>,
+    display_mode: DisplayMode,
+    item_sender: &Sender<Arc<dyn SkimItem>>,
+) {
+    if items.is_empty() {
+        println!("No items to display. Keep in mind that ppcli will only display non RDP/non WWW servers, and point of interests");
+        std::process::exit(0);
+    }
+    // the marker column is there only with --include-retired
+    let show_retired_col = items.iter().any(is_retired);
+    let mut actions: Vec<_> = items
+        .into_iter()
+        .flat_map(|item| actions::get_value(item, &config.actions))
+        .collect();
+    if !frecency_scores.is_empty() {
+        // stable sort: the actions which were never used keep their order
+        actions.sort_by_cached_key(|action| {
+            std::cmp::Reverse(
+                frecency_scores
+                    .get(&crate::scripting::action_reference(action))
+                    .copied()
+                    .unwrap_or(0),
+            )
+        });
+    }
+    let columns = if config.columns.is_empty() {
+        columns::default_columns()
+    } else {
+        config.columns.clone()
+    };
+    let widths = columns::compute_widths(
+        &columns,
+        &actions,
+        display_mode,
+        if show_retired_col {
+            RETIRED_COL_WIDTH
+        } else {
+            0
+        },
+    );
+    for action in actions {
+        let row = columns::render_row(&columns, &widths, &action, display_mode);
         let _ = item_sender.send(Arc::new(crate::MyItem {
-            display: render_row(&cols_spec, &action, display_mode),
+            display: if show_retired_col {
+                format!("{}{}", retired_col(&action.item, display_mode), row)
+            } else {
+                row
+            },
             inner: action,
         }));
     }
 }
 
-fn render_row(cols_spec: &[usize], action: &actions::Action, display_mode: DisplayMode) -> String {
-    let item = &action.item;
-    let mut col1 = item.project_name.clone();
-    col1.truncate(cols_spec[0]);
-    let col2 = item
-        .env
-        .as_ref()
-        .map(|env| display_env(env, display_mode))
-        .unwrap_or(if display_mode == DisplayMode::Color {
-            "-   "
-        } else {
-            "-  "
-        })
-        .to_string();
-    // col2.truncate(cols_spec[1]);
-    let mut col3 = render_type(&item.item_type).to_string();
-    col3.truncate(cols_spec[2]);
-    let mut col4 = item
-        .server_info
-        .as_ref()
-        .map(|si| si.server_desc.clone())
-        .unwrap_or_else(|| "-".to_string());
-    col4.truncate(cols_spec[3]);
-    let mut col5 = item
-        .poi_desc
-        .as_ref()
-        .cloned()
-        .unwrap_or_else(|| "".to_string());
-    col5.truncate(cols_spec[4]);
-    let mut col6 = action.desc.to_string();
-    col6.truncate(cols_spec[5]);
-    format!(
-        "{:<w1$} {:<w2$} {:<w3$} {:<w4$} {:<w5$}  {:<w6$}",
-        col1,
-        col2,
-        col3,
-        col4,
-        col5,
-        col6,
-        w1 = cols_spec[0],
-        w2 = cols_spec[1],
-        w3 = cols_spec[2],
-        w4 = cols_spec[3],
-        w5 = cols_spec[4],
-        w6 = cols_spec[5],
-    )
+// with --include-retired, a marker column before the others
+const RETIRED_COL_WIDTH: usize = 4;
+
+fn retired_col(item: &ItemOfInterest, display_mode: DisplayMode) -> &'static str {
+    match (is_retired(item), display_mode) {
+        (false, _) => "    ",
+        (true, DisplayMode::Color) => "\x1b[2mRET\x1b[0m ",
+        (true, DisplayMode::Plain) => "RET ",
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -280,7 +711,7 @@ pub enum DisplayMode {
     Color,
 }
 
-fn display_env(env: &EnvironmentType, display_mode: DisplayMode) -> &'static str {
+pub fn display_env(env: &EnvironmentType, display_mode: DisplayMode) -> &'static str {
     match (env, display_mode) {
         (EnvironmentType::EnvDevelopment, DisplayMode::Color) => "\x1b[32m\x1b[1m❚DEV\x1b[0m",
         (EnvironmentType::EnvDevelopment, DisplayMode::Plain) => "DEV",
@@ -293,7 +724,7 @@ fn display_env(env: &EnvironmentType, display_mode: DisplayMode) -> &'static str
     }
 }
 
-fn render_type(item_type: &ItemType) -> &'static str {
+pub fn render_type(item_type: &ItemType) -> &'static str {
     match item_type {
         ItemType::InterestItemType(InterestType::PoiCommandToRun) => "CMD",
         ItemType::InterestItemType(InterestType::PoiCommandTerminal) => "CMD",
@@ -306,5 +737,144 @@ fn render_type(item_type: &ItemType) -> &'static str {
         ItemType::ServerItemType(ServerType::SrvHttpOrProxy) => "HTT",
         ItemType::ServerItemType(ServerType::SrvReporting) => "REP",
         ItemType::ServerItemType(ServerType::SrvMonitoring) => "MON",
+        ItemType::DatabaseItemType => "DB",
+        ItemType::WebsiteItemType => "WWW",
+    }
+}
+
+const PREVIEW_KEYS_HELP: &str =
+    "[enter] run, [alt-enter] paste to prompt, [ctl-y] copy to clipboard, [ctl-n/p] history";
+const PREVIEW_NOTES_LINES: usize = 10;
+
+/// The fields of the item that are safe to display (never passwords)
+pub fn item_details(item: &ItemOfInterest) -> Vec<(&'static str, String)> {
+    let mut details = vec![("Project", item.project_name.clone())];
+    if let Some(env) = &item.env {
+        details.push((
+            "Environment",
+            display_env(env, DisplayMode::Plain).to_string(),
+        ));
     }
+    details.push(("Type", render_type(&item.item_type).to_string()));
+    if let Some(si) = &item.server_info {
+        details.push(("Server", si.server_desc.clone()));
+        details.push(("IP", si.server_ip.clone()));
+        details.push(("Username", si.server_username.clone()));
+        if si.server_retired {
+            details.push(("Retired", "yes".to_string()));
+        }
+    }
+    if let Some(desc) = &item.poi_desc {
+        details.push(("Description", desc.clone()));
+    }
+    if let Some(poi) = &item.poi_info {
+        details.push(("Path", poi.path.to_string_lossy().to_string()));
+    }
+    details.push(("Text", item.item_text.clone()));
+    if let Some(db) = &item.db_info {
+        details.push(("DB username", db.db_username.clone()));
+    }
+    if let Some(www) = &item.website_info {
+        details.push(("Site username", www.username.clone()));
+    }
+    if let Some(run_on) = &item.run_on {
+        details.push((
+            "Run on",
+            match run_on {
+                RunOn::RunOnServer => "server",
+                RunOn::RunOnClient => "client",
+            }
+            .to_string(),
+        ));
+    }
+    details
+}
+
+/// What the action would run or copy, hiding secrets
+pub fn action_value_for_display(action: &actions::Action) -> (&'static str, String) {
+    // never display secrets, the user may be sharing their screen
+    if action.is_secret {
+        ("Value", "<hidden>".to_string())
+    } else if action
+        .allowed_actions
+        .contains(&actions::AllowedAction::Run)
+    {
+        ("Command", (action.get_string)(&action.item).to_string())
+    } else {
+        ("Value", (action.get_string)(&action.item).to_string())
+    }
+}
+
+pub fn render_preview(action: &actions::Action) -> String {
+    let item = &action.item;
+    let mut lines = vec![PREVIEW_KEYS_HELP.to_string(), "".to_string()];
+    let mut details = item_details(item);
+    details.push(("Action", action.desc.to_string()));
+    details.push(action_value_for_display(action));
+    lines.extend(
+        details
+            .into_iter()
+            .map(|(label, value)| format!("{:<12} {}", format!("{}:", label), value)),
+    );
+    if let Some(si) = item
+        .server_info
+        .as_ref()
+        .filter(|si| !si.server_notes.is_empty())
+    {
+        lines.push("".to_string());
+        lines.push("Notes:".to_string());
+        lines.extend(
+            hide_note_passwords(&si.server_notes)
+                .lines()
+                .take(PREVIEW_NOTES_LINES)
+                .map(|l| l.to_string()),
+        );
+    }
+    lines.join("\n")
+}
+
+/// Whether the action matches all the words of the query, like the
+/// (exact, case-insensitive) skim matching
+pub fn matches_query(action: &actions::Action, query: &[String]) -> bool {
+    let haystack = item_details(&action.item)
+        .into_iter()
+        .map(|(_, value)| value)
+        .chain(std::iter::once(action.desc.to_string()))
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    query
+        .iter()
+        .flat_map(|q| q.split_whitespace())
+        .all(|word| haystack.contains(&word.to_lowercase()))
+}
+
+// notes may contain passwords, with the markdown extension [pass`secret`]
+// (with possibly more backticks, as for markdown inline code)
+fn hide_note_passwords(notes: &str) -> String {
+    let mut result = String::new();
+    let mut rest = notes;
+    while let Some(start) = rest.find("[pass`") {
+        let after_marker = &rest[start + "[pass".len()..];
+        let ticks = after_marker.len() - after_marker.trim_start_matches('`').len();
+        let closing = format!("{}]", "`".repeat(ticks));
+        match after_marker[ticks..].find(&closing) {
+            Some(end) => {
+                result.push_str(&rest[..start]);
+                result.push_str("[password]");
+                rest = &after_marker[ticks + end + closing.len()..];
+            }
+            None => break,
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[test]
+fn hide_note_passwords_various_backticks() {
+    assert_eq!(
+        "user [password], or [password] [pass`unclosed",
+        hide_note_passwords("user [pass`sec*ret`], or [pass`` s`e ``] [pass`unclosed")
+    );
 }
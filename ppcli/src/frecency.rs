@@ -0,0 +1,120 @@
+// remember which actions were used and when, to display the actions used
+// often and recently first. Every use is a line in the file:
+// <seconds since epoch>\t<reference of the action, like server:12:ssh shell>
+use crate::actions::Action;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// enough for the frecency of a few months of use
+const USAGE_LIMIT: usize = 1000;
+
+pub struct Usage {
+    timestamp: u64,
+    reference: String,
+}
+
+fn usage_file_path() -> PathBuf {
+    let mut path = projectpadsql::config_path();
+    path.push("cli-usage");
+    path
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The uses of the actions, oldest first. Empty if the file doesn't exist yet.
+pub fn read_usage() -> Vec<Usage> {
+    let file = match File::open(usage_file_path()) {
+        Ok(file) => file,
+        Err(_) => return vec![],
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, '\t');
+            match (parts.next().map(|t| t.parse::<u64>()), parts.next()) {
+                (Some(Ok(timestamp)), Some(reference)) => Some(Usage {
+                    timestamp,
+                    reference: reference.to_string(),
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+pub fn record_usage(orig_usage: &[Usage], actions: &[&Action]) -> Result<(), std::io::Error> {
+    let timestamp = now();
+    let start_index = (orig_usage.len() + actions.len()).saturating_sub(USAGE_LIMIT);
+    let file = File::create(usage_file_path())?;
+    let mut file = BufWriter::new(file);
+    for usage in orig_usage.iter().skip(start_index) {
+        writeln!(file, "{}\t{}", usage.timestamp, usage.reference)?;
+    }
+    for action in actions {
+        writeln!(
+            file,
+            "{}\t{}",
+            timestamp,
+            crate::scripting::action_reference(action)
+        )?;
+    }
+    Ok(())
+}
+
+// recent uses weigh more than old ones
+fn usage_weight(age_seconds: u64) -> u64 {
+    match age_seconds / (24 * 3600) {
+        0..=3 => 100,
+        4..=14 => 70,
+        15..=31 => 50,
+        32..=90 => 30,
+        _ => 10,
+    }
+}
+
+/// The frecency score of the actions, by reference. Unused actions aren't in the map.
+pub fn scores(usage: &[Usage]) -> HashMap<String, u64> {
+    scores_at(usage, now())
+}
+
+fn scores_at(usage: &[Usage], now: u64) -> HashMap<String, u64> {
+    let mut scores = HashMap::new();
+    for u in usage {
+        *scores.entry(u.reference.clone()).or_insert(0) +=
+            usage_weight(now.saturating_sub(u.timestamp));
+    }
+    scores
+}
+
+#[test]
+fn recent_uses_weigh_more() {
+    let day = 24 * 3600;
+    let now = 100 * day;
+    let usage = |days_ago: u64, reference: &str| Usage {
+        timestamp: now - days_ago * day,
+        reference: reference.to_string(),
+    };
+    let scores = scores_at(
+        &[
+            usage(60, "server:1:ssh shell"),
+            usage(50, "server:1:ssh shell"),
+            usage(40, "server:1:ssh shell"),
+            usage(1, "server:2:ssh shell"),
+            usage(0, "server:2:ssh shell"),
+        ],
+        now,
+    );
+    assert_eq!(Some(&90), scores.get("server:1:ssh shell"));
+    assert_eq!(Some(&200), scores.get("server:2:ssh shell"));
+    assert_eq!(None, scores.get("server:3:ssh shell"));
+}
@@ -1,25 +1,39 @@
+use actions::AllowedAction;
 use database::DisplayMode;
 use diesel::prelude::*;
+use projectpadsql::models::EnvironmentType;
+use projectpadsql::placeholders;
 use regex::Regex;
 use skim::prelude::*;
-use std::borrow::Borrow;
+use std::collections::HashMap;
 use std::env;
-use std::io::Write;
-use std::path::Path;
-use std::path::PathBuf;
+use std::io::{Read, Write};
 use std::process::{Command, Stdio};
 use std::sync::mpsc;
+use std::time::Duration;
 use structopt::clap::arg_enum;
 use structopt::StructOpt;
 mod actions;
+mod agent;
+mod askpass;
 mod autoupgrade;
+mod columns;
 pub mod config;
 mod database;
+mod frecency;
+mod parallel;
+mod prompt;
+mod run;
+mod scripting;
+mod secret_files;
 #[cfg_attr(target_os = "linux", path = "secretservice_linux.rs")]
 #[cfg_attr(not(target_os = "linux"), path = "secretservice_generic.rs")]
 mod secretservice;
+mod unlock;
 
 const ZSH_FUNCTION: &str = include_str!("../shell/integration.zsh");
+const BASH_FUNCTION: &str = include_str!("../shell/integration.bash");
+const FISH_FUNCTION: &str = include_str!("../shell/integration.fish");
 
 const MIN_SUPPORTED_DB_SCHEMA_VERSION: i32 = 21;
 const MAX_SUPPORTED_DB_SCHEMA_VERSION: i32 = 22;
@@ -41,6 +55,41 @@ struct Options {
     /// Print to stdout the function for a given shell
     #[structopt(long, default_value = "none")]
     print_shell_function: Shell,
+    /// Clear the clipboard after that many seconds, if it still contains the value read from stdin
+    #[structopt(long, hidden = true)]
+    clear_clipboard_after: Option<u64>,
+    /// Print the password for the ssh prompt, for SSH_ASKPASS
+    #[structopt(long, hidden = true)]
+    askpass: Option<String>,
+    /// Print a local port which is free, for the ssh tunnels of the database clients
+    #[structopt(long = "free-local-port", hidden = true)]
+    free_local_port: bool,
+    /// Only display items of projects whose name contains this text
+    #[structopt(long)]
+    project: Option<String>,
+    /// Only display items of that environment: dev, stg, uat or prd
+    #[structopt(long, parse(try_from_str = database::parse_env))]
+    env: Option<EnvironmentType>,
+    /// Only display items of that type: CMD, LOG, CFG, SRA...
+    #[structopt(long = "type", parse(try_from_str = database::parse_item_type))]
+    item_type: Option<String>,
+    /// Also display the retired servers and their items, marked RET
+    #[structopt(long = "include-retired")]
+    include_retired: bool,
+    /// Keep the default order instead of displaying the actions used often and recently first
+    #[structopt(long = "no-frecency")]
+    no_frecency: bool,
+    /// Read the database password from that file descriptor when there is no OS keyring
+    #[structopt(long = "password-fd")]
+    password_fd: Option<i32>,
+    /// Start with that query
+    #[structopt(long)]
+    query: Option<String>,
+    /// Run the action immediately if only one matches
+    #[structopt(long = "select-1")]
+    select_1: bool,
+    #[structopt(subcommand)]
+    subcommand: Option<scripting::SubCommand>,
 }
 
 arg_enum! {
@@ -48,6 +97,8 @@ arg_enum! {
     enum Shell {
         None,
         Zsh,
+        Bash,
+        Fish,
     }
 }
 
@@ -76,9 +127,7 @@ impl SkimItem for MyItem {
     }
 
     fn preview(&self, _context: PreviewContext) -> ItemPreview {
-        ItemPreview::Text(
-            "[enter] run, [alt-enter] paste to prompt, [ctl-y] copy to clipboard, [ctl-n/p] history".to_string(),
-        )
+        ItemPreview::Text(database::render_preview(&self.inner))
     }
 }
 
@@ -127,40 +176,79 @@ pub fn main() {
         }
         std::process::exit(0);
     }
-    if flag_options.print_shell_function == Shell::Zsh {
-        println!("\n{}", ZSH_FUNCTION);
+    if let Some(seconds) = flag_options.clear_clipboard_after {
+        clear_clipboard_after(seconds);
         std::process::exit(0);
     }
-    let db_pass = ok_or_exit!(
-        secretservice::get_keyring_pass().and_then(|r| r.ok_or_else(|| "no matching credentials".into())),
-        "Cannot find the database password in the OS keyring, aborting: did you run the projectpad GUI app to create a database first? {}",
-        1
-    );
-
-    let db_path_raw = projectpadsql::database_path();
-    let db_path = some_or_exit!(
-        db_path_raw.to_str(),
-        "Cannot find the database path on disk, aborting",
-        2
-    );
-
-    let conn = ok_or_exit!(
-        SqliteConnection::establish(db_path),
-        "Cannot open the database, aborting. {}",
-        3
-    );
-
-    ok_or_exit!(
-        projectpadsql::try_unlock_db(&conn, &db_pass),
-        "Failed unlocking the database with the password, aborting. {}",
-        4
-    );
-
-    ok_or_exit!(
-        check_db_version(&conn),
-        "{} https://github.com/emmanueltouzery/projectpad2",
-        5
+    if flag_options.free_local_port {
+        let port = ok_or_exit!(
+            actions::free_local_port(),
+            "ppcli: no free local port: {}",
+            1
+        );
+        println!("{}", port);
+        std::process::exit(0);
+    }
+    if let Some(prompt) = &flag_options.askpass {
+        let askpass_ref = ok_or_exit!(
+            env::var(askpass::ASKPASS_REF_ENV_VAR),
+            "ppcli askpass: the account reference is missing. {}",
+            1
+        );
+        let conn = open_unlocked_db(
+            flag_options.password_fd,
+            &config::read_config().unwrap_or_default(),
+        );
+        let password = ok_or_exit!(
+            askpass::get_password(&conn, &askpass_ref, prompt),
+            "ppcli askpass: {}",
+            1
+        );
+        println!("{}", password);
+        std::process::exit(0);
+    }
+    let shell_function = match flag_options.print_shell_function {
+        Shell::None => None,
+        Shell::Zsh => Some(ZSH_FUNCTION),
+        Shell::Bash => Some(BASH_FUNCTION),
+        Shell::Fish => Some(FISH_FUNCTION),
+    };
+    if let Some(shell_function) = shell_function {
+        println!("\n{}", shell_function);
+        std::process::exit(0);
+    }
+    let config = ok_or_exit!(
+        config::read_config(),
+        "Error reading the configuration file, aborting. {}",
+        7
     );
+    match &flag_options.subcommand {
+        Some(scripting::SubCommand::Lock) => {
+            agent::lock();
+            std::process::exit(0);
+        }
+        Some(scripting::SubCommand::Agent { timeout_minutes }) => {
+            let (_, db_pass) = open_unlocked_db_with_password(flag_options.password_fd, &config);
+            ok_or_exit!(
+                agent::run(db_pass, Duration::from_secs(*timeout_minutes * 60)),
+                "Error starting the agent, aborting. {}",
+                1
+            );
+            std::process::exit(0);
+        }
+        _ => {}
+    }
+    let conn = open_unlocked_db(flag_options.password_fd, &config);
+    let items_filter = database::ItemsFilter {
+        project: flag_options.project.clone(),
+        env: flag_options.env,
+        item_type: flag_options.item_type.clone(),
+        include_retired: flag_options.include_retired,
+    };
+    if let Some(subcommand) = flag_options.subcommand {
+        scripting::run_subcommand(&conn, &config, &items_filter, subcommand);
+        std::process::exit(0);
+    }
 
     // start a thread to, if we didn't check for 7 days, check whether there is
     // a new version of ppcli available (in a thread not to block the GUI).
@@ -199,10 +287,12 @@ pub fn main() {
         .bind(vec!["ctrl-p:previous-history", "ctrl-n:next-history"])
         .expect(Some("ctrl-y,alt-enter".to_string()))
         // .height(Some("50%"))
-        // .multi(true)
+        .multi(true)
         .preview(Some("")) // preview should be specified to enable preview window
-        .preview_window(Some("up:2"))
+        .preview_window(Some("up:40%:wrap"))
         .query_history(&history)
+        .query(flag_options.query.as_deref())
+        .select1(flag_options.select_1)
         .exact(true)
         .case(CaseMatching::Ignore)
         .build()
@@ -211,44 +301,164 @@ pub fn main() {
     let (tx_item, rx_item): (SkimItemSender, SkimItemReceiver) = unbounded();
 
     let display_mode = flag_options.display_mode;
-    std::thread::spawn(move || database::load_items(&conn, display_mode, &tx_item));
+    let items_config = config.clone();
+    let usage = frecency::read_usage();
+    let frecency_scores = if flag_options.no_frecency {
+        HashMap::new()
+    } else {
+        frecency::scores(&usage)
+    };
+    // the loader thread gives the connection back, to resolve references
+    // in the selected command
+    let items_loader = std::thread::spawn(move || {
+        database::send_items(
+            database::load_items(&conn, &items_config, &items_filter),
+            &items_config,
+            &frecency_scores,
+            display_mode,
+            &tx_item,
+        );
+        conn
+    });
 
     let (selected_items, query, accept_key) = Skim::run_with(&options, Some(rx_item))
         .map(|out| (out.selected_items, out.query, out.final_key))
         .unwrap_or_else(|| (Vec::new(), "".to_string(), Key::Enter));
+    // with --select-1, skim accepts the single match without a key press
+    let accept_key = if accept_key == Key::Null {
+        Key::Enter
+    } else {
+        accept_key
+    };
 
-    if let Some(item) = selected_items.get(0) {
-        if !query.is_empty() {
-            config::write_history(&history, &query, 100).unwrap();
+    if !selected_items.is_empty() && !query.is_empty() {
+        config::write_history(&history, &query, 100).unwrap();
+    }
+    let conn = items_loader.join().unwrap();
+    if selected_items.len() > 1 {
+        let selected_actions: Vec<_> = selected_items
+            .iter()
+            .map(|item| &(**item).as_any().downcast_ref::<MyItem>().unwrap().inner)
+            .collect();
+        if accept_key == Key::Enter {
+            let ran = parallel::run_selected(
+                &conn,
+                &selected_actions,
+                &config,
+                flag_options.display_mode,
+                flag_options.shell_integration_mode,
+            );
+            if ran {
+                let _ = frecency::record_usage(&usage, &selected_actions);
+            }
+        } else {
+            eprintln!("Several items can only be run, select a single item to copy it");
         }
-
+    } else if let Some(item) = selected_items.get(0) {
         // this pattern from the skim apidocs for SkimItem, and also
         // https://stackoverflow.com/a/26128001/516188
         let myitem = (**item).as_any().downcast_ref::<MyItem>().unwrap();
 
         let action = &myitem.inner;
         let action_str = &(action.get_string)(&action.item);
-        match accept_key {
-            Key::Ctrl('y') if flag_options.shell_integration_mode => println!("C\x00{}", action_str),
-            Key::Ctrl('y') => copy_command_to_clipboard(action_str),
-            Key::AltEnter if flag_options.shell_integration_mode => println!("P\x00{}", action_str),
-            Key::AltEnter =>
-            // copy to command-line if run is not allowed for that action
-                    // if !val_action.allowed_actions.contains(&AllowedAction::Run) =>
-            {
-                write_command_line_to_terminal(action_str)
+        // some actions can't be run (for instance copying a password),
+        // in that case enter falls back on something the action supports.
+        let accept_key = match accept_key {
+            Key::Enter if !action.allowed_actions.contains(&AllowedAction::Run) => {
+                if action
+                    .allowed_actions
+                    .contains(&AllowedAction::CopyToPrompt)
+                {
+                    Key::AltEnter
+                } else {
+                    Key::Ctrl('y')
+                }
+            }
+            k => k,
+        };
+        if accept_key == Key::Enter {
+            let commands = ok_or_exit!(
+                run::prepare(&conn, &[action], &config, flag_options.display_mode, false),
+                "Failed preparing the command, aborting. {}",
+                8
+            );
+            match commands.as_ref().and_then(|c| c.first()) {
+                None => {}
+                Some(command) if flag_options.shell_integration_mode => {
+                    // the shell will run the command after we exit, it's
+                    // in charge of removing the secret files when it's done
+                    let secret_files = ok_or_exit!(
+                        run::write_secret_files(std::slice::from_ref(command)),
+                        "{}, aborting",
+                        6
+                    );
+                    let secret_paths: String = secret_files
+                        .iter()
+                        .map(|f| format!("\x00{}", f.path.to_string_lossy()))
+                        .collect();
+                    let command_env = command
+                        .env()
+                        .iter()
+                        .map(|(k, v)| format!("{}={}", k, shell_words::quote(v)))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    let _ = frecency::record_usage(&usage, &[action]);
+                    print!(
+                        "R\x00{}\x00{}\x00{}{}",
+                        command.command,
+                        command
+                            .folder()
+                            .map(|p| p.to_string_lossy().to_string())
+                            .unwrap_or_else(|| "".to_string()),
+                        command_env,
+                        secret_paths
+                    )
+                }
+                Some(command) => {
+                    // only the commands which could be launched count for the ranking
+                    if ok_or_exit!(run::run(command), "{}, aborting", 6).is_some() {
+                        let _ = frecency::record_usage(&usage, &[action]);
+                    }
+                }
+            }
+        } else {
+            // references to other items, like {{server:"DB prod".ip}}, get their
+            // current value. Secrets such as passwords are copied as they are.
+            let is_secret = action.is_secret || placeholders::has_secret_references(action_str);
+            let action_str = &if action.is_secret {
+                action_str.clone()
+            } else {
+                Cow::Owned(ok_or_exit!(
+                    placeholders::resolve_references(&conn, action.item.project_id, action_str),
+                    "Failed resolving the references of the command, aborting. {}",
+                    8
+                ))
+            };
+            let _ = frecency::record_usage(&usage, &[action]);
+            // in shell integration mode we print fields separated by NUL for the shell
+            // function, without trailing newline which would end up in the last field.
+            match accept_key {
+                Key::Ctrl('y') if flag_options.shell_integration_mode => {
+                    print!("C\x00{}", action_str);
+                    if is_secret {
+                        spawn_clipboard_clear(action_str, config.clipboard_clear_seconds);
+                    }
+                }
+                Key::Ctrl('y') => {
+                    copy_command_to_clipboard(action_str);
+                    if is_secret {
+                        spawn_clipboard_clear(action_str, config.clipboard_clear_seconds);
+                    }
+                }
+                Key::AltEnter if flag_options.shell_integration_mode => {
+                    print!("P\x00{}", action_str)
+                }
+                // copy to command-line if run is not allowed for that action
+                Key::AltEnter => write_command_line_to_terminal(action_str),
+                _ => {}
             }
-            Key::Enter if flag_options.shell_integration_mode => println!(
-                "R\x00{}\x00{}", action_str, &run_command_folder(&action)
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_else(|| "".to_string())),
-            Key::Enter => run_command(
-                action_str,
-                &run_command_folder(&action)
-                    .unwrap_or_else(|| dirs::home_dir().unwrap()),
-            ),
-            _ => {}
         }
+        let _ = std::io::stdout().flush();
     }
 
     // try_recv, don't want to block there... don't want
@@ -267,11 +477,45 @@ pub fn main() {
     }
 }
 
-fn run_command_folder(action: &actions::Action) -> Option<PathBuf> {
-    Some(&action.item)
-        .filter(|p| p.server_info.is_none()) // remote paths are not relevant!
-        .and_then(|i| i.poi_info.as_ref())
-        .map(|p| p.path.clone())
+fn open_unlocked_db(password_fd: Option<i32>, config: &config::Config) -> SqliteConnection {
+    open_unlocked_db_with_password(password_fd, config).0
+}
+
+fn open_unlocked_db_with_password(
+    password_fd: Option<i32>,
+    config: &config::Config,
+) -> (SqliteConnection, String) {
+    let db_pass = ok_or_exit!(
+        unlock::get_db_password(password_fd, config),
+        "Cannot get the database password, aborting: did you run the projectpad GUI app to create a database first? {}",
+        1
+    );
+
+    let db_path_raw = projectpadsql::database_path();
+    let db_path = some_or_exit!(
+        db_path_raw.to_str(),
+        "Cannot find the database path on disk, aborting",
+        2
+    );
+
+    let conn = ok_or_exit!(
+        SqliteConnection::establish(db_path),
+        "Cannot open the database, aborting. {}",
+        3
+    );
+
+    ok_or_exit!(
+        projectpadsql::try_unlock_db(&conn, &db_pass),
+        "Failed unlocking the database with the password, aborting. {}",
+        4
+    );
+
+    ok_or_exit!(
+        check_db_version(&conn),
+        "{} https://github.com/emmanueltouzery/projectpad2",
+        5
+    );
+    (conn, db_pass)
 }
 
 fn check_db_version(conn: &SqliteConnection) -> Result<(), Box<dyn std::error::Error>> {
@@ -289,33 +533,6 @@ fn check_db_version(conn: &SqliteConnection) -> Result<(), Box<dyn std::error::E
     Ok(())
 }
 
-fn run_command(command_line: &str, cur_dir: &Path) {
-    let cl_elts = shell_words::split(command_line).unwrap_or_else(|e| {
-        println!("Couldn't parse the command: {}: {}", command_line, e);
-        Vec::new()
-    });
-    if !cl_elts.is_empty() {
-        // the reason for the println is that some commands need
-        // some time before they print out any output -- for instance
-        // ssh on a far, slow server. With this println we give some
-        // feedback to the user.
-        let actual_dir = if cur_dir.as_os_str().is_empty() {
-            Cow::Owned(std::env::current_dir().unwrap())
-        } else {
-            Cow::Borrowed(cur_dir)
-        };
-        println!("Running {} in folder {:?}...", command_line, actual_dir);
-        Command::new(cl_elts[0].clone())
-            .args(cl_elts.iter().skip(1))
-            .current_dir::<&Path>(actual_dir.borrow())
-            .status()
-            .map(|_| ())
-            .unwrap_or_else(|e| {
-                println!("Error launching process: {}", e);
-            });
-    }
-}
-
 fn copy_command_to_clipboard(command_line: &str) {
     // there are libraries for that in rust, earlier i was using
     // clibpoard-ext, but:
@@ -330,11 +547,14 @@ fn copy_command_to_clipboard(command_line: &str) {
 
     // detect wayland or X11 https://unix.stackexchange.com/a/559950/36566
     if std::env::var("WAYLAND_DISPLAY").is_ok() {
+        // through stdin, the command-line of processes is visible to other users
         match Command::new("wl-copy")
-            .arg(command_line)
+            .stdin(Stdio::piped())
             .spawn()
-            .and_then(|mut p| p.wait())
-        {
+            .and_then(|mut p| {
+                p.stdin.take().unwrap().write_all(command_line.as_bytes())?;
+                p.wait()
+            }) {
             Result::Err(e) => eprintln!("Failed to invoke wl-copy: {}", e),
             Result::Ok(s) if !s.success() => eprintln!("Got error status from wl-copy: {}", s),
             _ => {}
@@ -369,22 +589,114 @@ fn copy_command_to_clipboard(command_line: &str) {
     }
 }
 
+fn spawn_clipboard_clear(value: &str, seconds: u64) {
+    if seconds == 0 {
+        return;
+    }
+    // we must not block: we spawn ourselves in the background. Close stdout,
+    // otherwise the shell integration would wait for the child to exit.
+    // Hand over the value through stdin so that it doesn't appear in
+    // the process list.
+    let spawn_res = env::current_exe()
+        .and_then(|exe| {
+            Command::new(exe)
+                .arg("--clear-clipboard-after")
+                .arg(seconds.to_string())
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+        })
+        .and_then(|mut child| child.stdin.take().unwrap().write_all(value.as_bytes()));
+    if let Err(e) = spawn_res {
+        eprintln!("Failed to schedule the clearing of the clipboard: {}", e);
+    }
+}
+
+fn clear_clipboard_after(seconds: u64) {
+    let mut value = String::new();
+    if std::io::stdin().read_to_string(&mut value).is_err() {
+        return;
+    }
+    std::thread::sleep(Duration::from_secs(seconds));
+    // the user may have copied something else in the meantime,
+    // leave the clipboard alone in that case. The shell integration
+    // may have added a trailing newline.
+    if read_clipboard()
+        .as_deref()
+        .map(|c| c.trim_end_matches('\n'))
+        == Some(value.trim_end_matches('\n'))
+    {
+        clear_clipboard();
+    }
+}
+
+fn read_clipboard() -> Option<String> {
+    let output = if std::env::var("WAYLAND_DISPLAY").is_ok() {
+        Command::new("wl-paste").arg("--no-newline").output()
+    } else {
+        Command::new("xsel")
+            .args(&["--clipboard", "--output"])
+            .output()
+    };
+    output
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+}
+
+fn clear_clipboard() {
+    let status = if std::env::var("WAYLAND_DISPLAY").is_ok() {
+        Command::new("wl-copy").arg("--clear").status()
+    } else {
+        Command::new("xsel")
+            .args(&["--clipboard", "--delete"])
+            .status()
+    };
+    if let Err(e) = status {
+        eprintln!("Failed clearing the clipboard: {}", e);
+    }
+}
+
 fn write_command_line_to_terminal(command_line: &str) {
-    // https://unix.stackexchange.com/questions/213799/can-bash-write-to-its-own-input-stream/213821#213821
-    unsafe {
-        for byte in command_line.bytes() {
-            libc::ioctl(libc::STDIN_FILENO, libc::TIOCSTI, &byte);
+    if write_command_line_tiocsti(command_line) {
+        return;
+    }
+    // linux 6.2+ disables TIOCSTI by default (dev.tty.legacy_tiocsti=0)
+    if env::var("TMUX").is_ok() {
+        match Command::new("tmux")
+            .args(&["send-keys", "-l", "--", command_line])
+            .status()
+        {
+            Ok(s) if s.success() => {
+                eprintln!("The terminal doesn't allow writing to the prompt, pasted the command through tmux.");
+                return;
+            }
+            Ok(s) => eprintln!("Got error status from tmux send-keys: {}", s),
+            Err(e) => eprintln!("Failed to invoke tmux send-keys: {}", e),
         }
     }
+    // OSC 52: ask the terminal to copy the text to the clipboard.
+    // not all terminals support it, so also print the command.
+    print!("\x1b]52;c;{}\x07", base64::encode(command_line));
+    let _ = std::io::stdout().flush();
+    eprintln!(
+        "The terminal doesn't allow writing to the prompt. The command was copied to the clipboard \
+         if the terminal supports OSC 52. The shell integration (--print-shell-function) can paste \
+         to the prompt. The command:\n{}",
+        command_line
+    );
+}
 
-    // this code requires tmux. the ioctl is considered unsafe by some,
-    // the tmux way could become more portable in the future possible?
-    //
-    // std::process::Command::new("tmux")
-    //     .arg("send-key")
-    //     .arg(&myitem.command)
-    //     .status()
-    //     .unwrap();
+fn write_command_line_tiocsti(command_line: &str) -> bool {
+    // https://unix.stackexchange.com/questions/213799/can-bash-write-to-its-own-input-stream/213821#213821
+    for byte in command_line.bytes() {
+        if unsafe { libc::ioctl(libc::STDIN_FILENO, libc::TIOCSTI, &byte) } != 0 {
+            // if we fail, it's for the first byte, so we didn't write anything
+            return false;
+        }
+    }
+    true
 }
 
 #[test]
@@ -0,0 +1,136 @@
+// run the same action on several items at once (for instance "tail log" on
+// all the application servers of an environment). The output of each command
+// is prefixed with the server it comes from.
+use crate::actions::{Action, AllowedAction};
+use crate::config::Config;
+use crate::database::DisplayMode;
+use crate::run::PreparedCommand;
+use crate::secret_files;
+use diesel::sqlite::SqliteConnection;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, ExitStatus, Stdio};
+
+/// Returns whether the commands ran: false if they were refused at the
+/// confirmation or couldn't be launched
+pub fn run_selected(
+    conn: &SqliteConnection,
+    selected_actions: &[&Action],
+    config: &Config,
+    display_mode: DisplayMode,
+    output_to_stderr: bool,
+) -> bool {
+    let action_desc = &selected_actions[0].desc;
+    if selected_actions.iter().any(|a| &a.desc != action_desc) {
+        eprintln!("All the selected rows must have the same action to run them together");
+        return false;
+    }
+    if !selected_actions[0]
+        .allowed_actions
+        .contains(&AllowedAction::Run)
+    {
+        eprintln!("The '{}' action can't be run", action_desc);
+        return false;
+    }
+    let commands = match crate::run::prepare(conn, selected_actions, config, display_mode, false) {
+        Ok(Some(commands)) => commands,
+        Ok(None) => return false,
+        Err(e) => {
+            eprintln!("Failed preparing the commands, aborting. {}", e);
+            return false;
+        }
+    };
+    let secret_files = match crate::run::write_secret_files(&commands) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}, aborting.", e);
+            return false;
+        }
+    };
+
+    let labels: Vec<_> = selected_actions.iter().map(|a| item_label(a)).collect();
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let handles: Vec<_> = commands
+        .iter()
+        .zip(labels.iter())
+        .map(|(command, label)| {
+            let prefix = format!("{:<width$} | ", label, width = label_width);
+            spawn_command(command, prefix, output_to_stderr)
+        })
+        .collect();
+    let launched = handles.iter().any(Option::is_some);
+    let statuses: Vec<_> = handles
+        .into_iter()
+        .map(|h| h.and_then(|h| h.join().ok().flatten()))
+        .collect();
+    secret_files::remove_secret_files(&secret_files);
+
+    eprintln!();
+    for (label, status) in labels.iter().zip(statuses) {
+        let status_desc = match status.map(|s| s.code()) {
+            Some(Some(code)) => format!("exit code {}", code),
+            Some(None) => "killed by a signal".to_string(),
+            None => "failed to launch".to_string(),
+        };
+        eprintln!("{:<width$}: {}", label, status_desc, width = label_width);
+    }
+    launched
+}
+
+fn item_label(action: &Action) -> String {
+    action
+        .item
+        .server_info
+        .as_ref()
+        .map(|si| si.server_desc.clone())
+        .or_else(|| action.item.poi_desc.clone())
+        .unwrap_or_else(|| action.item.project_name.clone())
+}
+
+fn spawn_command(
+    command: &PreparedCommand,
+    prefix: String,
+    output_to_stderr: bool,
+) -> Option<std::thread::JoinHandle<Option<ExitStatus>>> {
+    let env = command.env();
+    let cl_elts = match crate::run::command_line_elts(&command.command, &env) {
+        Ok(elts) if !elts.is_empty() => elts,
+        _ => {
+            eprintln!("{}Couldn't parse the command: {}", prefix, command.command);
+            return None;
+        }
+    };
+    let mut child = Command::new(&cl_elts[0])
+        .args(cl_elts.iter().skip(1))
+        .current_dir(
+            command
+                .folder()
+                .unwrap_or_else(|| dirs::home_dir().unwrap()),
+        )
+        .envs(env.iter().cloned())
+        // the commands can't be interactive, we can't share the terminal
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| eprintln!("{}Error launching process: {}", prefix, e))
+        .ok()?;
+    let stdout = child.stdout.take()?;
+    let stderr = child.stderr.take()?;
+    Some(std::thread::spawn(move || {
+        let stderr_prefix = prefix.clone();
+        let stderr_thread = std::thread::spawn(move || forward_lines(stderr, &stderr_prefix, true));
+        forward_lines(stdout, &prefix, output_to_stderr);
+        let _ = stderr_thread.join();
+        child.wait().ok()
+    }))
+}
+
+fn forward_lines(output: impl Read, prefix: &str, to_stderr: bool) {
+    for line in BufReader::new(output).lines() {
+        match line {
+            Ok(line) if to_stderr => eprintln!("{}{}", prefix, line),
+            Ok(line) => println!("{}{}", prefix, line),
+            Err(_) => break,
+        }
+    }
+}
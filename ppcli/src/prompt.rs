@@ -0,0 +1,128 @@
+// questions to the user after skim exited. We talk to /dev/tty directly:
+// with the shell integration our stdout is captured by the shell, and the
+// shell may have left the terminal in raw mode (zle for instance).
+use crate::actions::Action;
+use crate::database::{self, DisplayMode};
+use projectpadsql::placeholders::{self, Placeholder};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::io::AsRawFd;
+
+pub fn read_line_from_tty(prompt: &str) -> std::io::Result<String> {
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    with_cooked_tty(&tty, true, || {
+        (&tty).write_all(prompt.as_bytes())?;
+        let mut line = String::new();
+        BufReader::new(&tty).read_line(&mut line)?;
+        Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+    })
+}
+
+/// Read a line without echoing it, for passwords
+pub fn read_password_from_tty(prompt: &str) -> std::io::Result<String> {
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    let password = with_cooked_tty(&tty, false, || {
+        (&tty).write_all(prompt.as_bytes())?;
+        let mut line = String::new();
+        BufReader::new(&tty).read_line(&mut line)?;
+        Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+    });
+    // the newline the user typed wasn't echoed
+    (&tty).write_all(b"\n")?;
+    password
+}
+
+// line editing and newline translation on for the duration of the read,
+// echo too unless we're reading a password
+fn with_cooked_tty<T>(
+    tty: &File,
+    echo: bool,
+    f: impl FnOnce() -> std::io::Result<T>,
+) -> std::io::Result<T> {
+    let fd = tty.as_raw_fd();
+    let mut orig_termios = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(fd, &mut orig_termios) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut termios = orig_termios;
+    termios.c_lflag |= libc::ICANON;
+    if echo {
+        termios.c_lflag |= libc::ECHO;
+    } else {
+        termios.c_lflag &= !libc::ECHO;
+    }
+    termios.c_iflag |= libc::ICRNL;
+    termios.c_oflag |= libc::OPOST | libc::ONLCR;
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) };
+    let result = f();
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &orig_termios) };
+    result
+}
+
+/// Show the commands in red and ask whether to run them. Without a terminal
+/// to ask the question, the answer is no.
+pub fn confirm_run(commands: &[(&Action, &str)], display_mode: DisplayMode) -> bool {
+    let (red, reset) = match display_mode {
+        DisplayMode::Color => ("\x1b[31m\x1b[1m", "\x1b[0m"),
+        DisplayMode::Plain => ("", ""),
+    };
+    let commands_desc: String = commands
+        .iter()
+        .map(|(action, command)| {
+            format!(
+                "  [{}] {}\n",
+                action
+                    .item
+                    .env
+                    .as_ref()
+                    .map(|env| database::display_env(env, DisplayMode::Plain))
+                    .unwrap_or("-"),
+                command
+            )
+        })
+        .collect();
+    let prompt = format!(
+        "{}About to run:\n{}Continue? [y/N] {}",
+        red, commands_desc, reset
+    );
+    match read_line_from_tty(&prompt) {
+        Ok(answer) => ["y", "yes"].contains(&answer.trim().to_lowercase().as_str()),
+        Err(e) => {
+            eprintln!(
+                "Cannot ask for confirmation, not running the command: {}",
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Prompt for the {{ask:...}} parameters of the command. A parameter which
+/// was already answered (for another command) is not asked again.
+/// References to other items are left as they are.
+pub fn fill_ask_placeholders(
+    command: &str,
+    answers: &mut HashMap<String, String>,
+) -> Result<String, String> {
+    placeholders::replace_placeholders(command, |placeholder, source| match placeholder {
+        Placeholder::Reference { .. } => Ok(source.to_string()),
+        Placeholder::Ask { name, default } => {
+            if let Some(answer) = answers.get(name) {
+                return Ok(answer.clone());
+            }
+            let default = default.as_deref().map(placeholders::resolve_ask_default);
+            let prompt = match &default {
+                Some(default) => format!("{} [{}]: ", name, default),
+                None => format!("{}: ", name),
+            };
+            let answer = read_line_from_tty(&prompt).map_err(|e| e.to_string())?;
+            let answer = match default {
+                Some(default) if answer.is_empty() => default,
+                _ => answer,
+            };
+            answers.insert(name.clone(), answer.clone());
+            Ok(answer)
+        }
+    })
+}
@@ -0,0 +1,164 @@
+// running the commands of actions, the same way from the interactive list,
+// for several rows at once and from `ppcli run`: the references to other
+// items are resolved (their passwords go through the environment), the
+// {{ask:...}} parameters are asked for, the commands are confirmed if needed,
+// and the secret files they need exist while they run.
+use crate::actions::{self, Action};
+use crate::config::Config;
+use crate::database::DisplayMode;
+use crate::secret_files::{self, SecretFile};
+use diesel::sqlite::SqliteConnection;
+use projectpadsql::placeholders;
+use std::borrow::{Borrow, Cow};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+pub struct PreparedCommand<'a> {
+    pub action: &'a Action,
+    pub command: String,
+    // the passwords of the references, PPCLI_REF_n
+    reference_env: Vec<(String, String)>,
+}
+
+impl<'a> PreparedCommand<'a> {
+    /// The environment variables for the command: for askpass, and
+    /// the passwords of the references to other items
+    pub fn env(&self) -> Vec<(&str, String)> {
+        actions::get_command_env(self.action)
+            .into_iter()
+            .chain(
+                self.reference_env
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.clone())),
+            )
+            .collect()
+    }
+
+    /// The folder to run the command in, empty for the current folder
+    pub fn folder(&self) -> Option<PathBuf> {
+        Some(&self.action.item)
+            .filter(|p| p.server_info.is_none()) // remote paths are not relevant!
+            .and_then(|i| i.poi_info.as_ref())
+            .map(|p| p.path.clone())
+    }
+}
+
+/// The commands of the actions, ready to run. None if the user
+/// didn't confirm them. The parameters which have the same name
+/// in several commands are asked once.
+pub fn prepare<'a>(
+    conn: &SqliteConnection,
+    actions: &[&'a Action],
+    config: &Config,
+    display_mode: DisplayMode,
+    skip_confirmation: bool,
+) -> Result<Option<Vec<PreparedCommand<'a>>>, String> {
+    let mut answers = HashMap::new();
+    let mut commands = vec![];
+    for &action in actions {
+        let (command, reference_env) = placeholders::resolve_references_secrets_to_env(
+            conn,
+            action.item.project_id,
+            &(action.get_string)(&action.item),
+        )?;
+        commands.push(PreparedCommand {
+            action,
+            command: crate::prompt::fill_ask_placeholders(&command, &mut answers)?,
+            reference_env,
+        });
+    }
+    // some actions must be confirmed before running them, for instance on PRD
+    if !skip_confirmation && actions.iter().any(|a| config.needs_confirmation(a)) {
+        let to_confirm: Vec<_> = commands
+            .iter()
+            .map(|c| (c.action, c.command.as_str()))
+            .collect();
+        if !crate::prompt::confirm_run(&to_confirm, display_mode) {
+            return Ok(None);
+        }
+    }
+    Ok(Some(commands))
+}
+
+/// Write the files with secrets that the commands need. Several commands
+/// may share the same files (for instance the ssh key of a server).
+pub fn write_secret_files(commands: &[PreparedCommand]) -> Result<Vec<SecretFile>, String> {
+    let mut files: Vec<_> = commands
+        .iter()
+        .flat_map(|c| actions::get_secret_files(c.action))
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files.dedup_by(|a, b| a.path == b.path);
+    secret_files::write_secret_files(&files).map_err(|e| {
+        format!(
+            "Failed writing the temporary files needed by the command: {}",
+            e
+        )
+    })?;
+    Ok(files)
+}
+
+/// Run the command in the terminal, with its secret files. None if it couldn't be launched.
+pub fn run(command: &PreparedCommand) -> Result<Option<ExitStatus>, String> {
+    let files = write_secret_files(std::slice::from_ref(command))?;
+    let status = run_command(
+        &command.command,
+        &command
+            .folder()
+            .unwrap_or_else(|| dirs::home_dir().unwrap()),
+        &command.env(),
+    );
+    secret_files::remove_secret_files(&files);
+    Ok(status)
+}
+
+/// The program to run and its parameters. When the passwords of references
+/// are given through the environment, a shell must expand them.
+pub fn command_line_elts(
+    command_line: &str,
+    env: &[(&str, String)],
+) -> Result<Vec<String>, shell_words::ParseError> {
+    if env
+        .iter()
+        .any(|(k, _)| k.starts_with(placeholders::REFERENCE_ENV_VAR_PREFIX))
+    {
+        Ok(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            command_line.to_string(),
+        ])
+    } else {
+        shell_words::split(command_line)
+    }
+}
+
+fn run_command(command_line: &str, cur_dir: &Path, env: &[(&str, String)]) -> Option<ExitStatus> {
+    let cl_elts = command_line_elts(command_line, env).unwrap_or_else(|e| {
+        println!("Couldn't parse the command: {}: {}", command_line, e);
+        Vec::new()
+    });
+    if !cl_elts.is_empty() {
+        // the reason for the println is that some commands need
+        // some time before they print out any output -- for instance
+        // ssh on a far, slow server. With this println we give some
+        // feedback to the user.
+        let actual_dir = if cur_dir.as_os_str().is_empty() {
+            Cow::Owned(std::env::current_dir().unwrap())
+        } else {
+            Cow::Borrowed(cur_dir)
+        };
+        println!("Running {} in folder {:?}...", command_line, actual_dir);
+        Command::new(cl_elts[0].clone())
+            .args(cl_elts.iter().skip(1))
+            .current_dir::<&Path>(actual_dir.borrow())
+            .envs(env.iter().cloned())
+            .status()
+            .map_err(|e| {
+                println!("Error launching process: {}", e);
+            })
+            .ok()
+    } else {
+        None
+    }
+}
@@ -0,0 +1,390 @@
+// non-interactive subcommands, to use projectpad entries from
+// makefiles, CI scripts and other tools.
+use crate::actions::{self, Action, AllowedAction};
+use crate::config::Config;
+use crate::database::{self, DisplayMode};
+use diesel::sqlite::SqliteConnection;
+use projectpadsql::placeholders::{self, ReferenceKind};
+use serde_derive::Serialize;
+use structopt::StructOpt;
+
+// the same as in the interactive mode
+const EXIT_SECRET_FILES_FAILED: i32 = 6;
+const EXIT_PREPARE_FAILED: i32 = 8;
+// on top of the exit codes for opening the database
+const EXIT_NO_MATCH: i32 = 10;
+const EXIT_SEVERAL_MATCHES: i32 = 11;
+const EXIT_INVALID_REFERENCE: i32 = 12;
+const EXIT_LAUNCH_FAILED: i32 = 13;
+const EXIT_CANCELLED: i32 = 14;
+const EXIT_BROKEN_REFERENCES: i32 = 15;
+const EXIT_NEEDS_SECRET_FILES: i32 = 16;
+
+#[derive(StructOpt)]
+pub enum SubCommand {
+    /// List all the actions, one per line: reference, project, environment, type, server, description
+    List {
+        /// Output JSON instead of tab-separated values
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Display an item and its actions, given a reference like server_point_of_interest:12
+    Show {
+        reference: String,
+        /// Output JSON instead of text
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Run an action, given a reference like server:12:ssh shell. Without the action,
+    /// run the first runnable action of the item. Exits with the exit code of the command
+    Run {
+        reference: String,
+        /// Don't ask for confirmation, even if the configuration requires it
+        #[structopt(long)]
+        yes: bool,
+    },
+    /// Print the command for the single runnable action matching the query, fails if several match
+    Cmd { query: Vec<String> },
+    /// List the entries with invalid placeholders or references to items which
+    /// don't exist anymore, for instance after renaming a server
+    Check,
+    /// Run a command with values from projectpad in its environment, for instance
+    /// exec --env PGPASSWORD=db:12.password -- psql. The values are given only to
+    /// the command, they don't end up in the shell history or on disk
+    Exec {
+        /// NAME=<kind>:<id>.<field>, the kind being server, db or www
+        #[structopt(long = "env", number_of_values = 1)]
+        env: Vec<String>,
+        #[structopt(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Keep the database password in memory for the next ppcli invocations, until it
+    /// wasn't used for the timeout or until ppcli lock. Start it with ppcli agent &
+    Agent {
+        /// Stop after that many minutes without use
+        #[structopt(long = "timeout", default_value = "30")]
+        timeout_minutes: u64,
+    },
+    /// Stop the agent, the next ppcli invocations will unlock the database again
+    Lock,
+}
+
+#[derive(Serialize)]
+struct ActionOutput<'a> {
+    reference: String,
+    table: &'a str,
+    id: i32,
+    action: &'a str,
+    project: &'a str,
+    environment: Option<&'static str>,
+    item_type: &'static str,
+    server: Option<&'a str>,
+    description: Option<&'a str>,
+    runnable: bool,
+    retired: bool,
+}
+
+#[derive(Serialize)]
+struct ShowActionOutput<'a> {
+    action: &'a str,
+    // None for secrets, and for commands which need secret files
+    // (ssh keys, database passwords...) that only `ppcli run` writes
+    command: Option<String>,
+    runnable: bool,
+}
+
+#[derive(Serialize)]
+struct ShowOutput<'a> {
+    reference: String,
+    details: serde_json::Map<String, serde_json::Value>,
+    actions: Vec<ShowActionOutput<'a>>,
+}
+
+fn item_reference(item: &database::ItemOfInterest) -> String {
+    format!("{}:{}", item.sql_table, item.id)
+}
+
+pub fn action_reference(action: &Action) -> String {
+    format!("{}:{}", item_reference(&action.item), action.desc)
+}
+
+fn is_runnable(action: &Action) -> bool {
+    action.allowed_actions.contains(&AllowedAction::Run)
+}
+
+fn action_output(action: &Action) -> ActionOutput {
+    let item = &action.item;
+    ActionOutput {
+        reference: action_reference(action),
+        table: &item.sql_table,
+        id: item.id,
+        action: &action.desc,
+        project: &item.project_name,
+        environment: item
+            .env
+            .as_ref()
+            .map(|env| database::display_env(env, DisplayMode::Plain)),
+        item_type: database::render_type(&item.item_type),
+        server: item.server_info.as_ref().map(|si| si.server_desc.as_str()),
+        description: item.poi_desc.as_deref(),
+        runnable: is_runnable(action),
+        retired: database::is_retired(item),
+    }
+}
+
+fn load_actions(
+    conn: &SqliteConnection,
+    config: &Config,
+    filter: &database::ItemsFilter,
+) -> Vec<Action> {
+    database::load_items(conn, config, filter)
+        .into_iter()
+        .flat_map(|item| actions::get_value(item, &config.actions))
+        .collect()
+}
+
+/// Find the actions for a reference like server:12 or server:12:ssh shell
+fn find_actions<'a>(actions: &'a [Action], reference: &str) -> Vec<&'a Action> {
+    let mut parts = reference.splitn(3, ':');
+    let (table, id, action_desc) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(table), Some(id), action_desc) => (table, id, action_desc),
+        _ => {
+            eprintln!(
+                "Invalid reference {}, expected <table>:<id>[:action]",
+                reference
+            );
+            std::process::exit(EXIT_INVALID_REFERENCE);
+        }
+    };
+    let id = id.parse::<i32>().unwrap_or_else(|_| {
+        eprintln!("Invalid id in the reference {}", reference);
+        std::process::exit(EXIT_INVALID_REFERENCE);
+    });
+    let matches: Vec<_> = actions
+        .iter()
+        .filter(|a| a.item.sql_table == table && a.item.id == id)
+        .filter(|a| action_desc.is_none_or(|d| a.desc.eq_ignore_ascii_case(d)))
+        .collect();
+    if matches.is_empty() {
+        eprintln!("No item matches {}", reference);
+        std::process::exit(EXIT_NO_MATCH);
+    }
+    matches
+}
+
+pub fn run_subcommand(
+    conn: &SqliteConnection,
+    config: &Config,
+    filter: &database::ItemsFilter,
+    subcommand: SubCommand,
+) {
+    let actions = load_actions(conn, config, filter);
+    match subcommand {
+        SubCommand::List { json } => list(&actions, json),
+        SubCommand::Show { reference, json } => show(&find_actions(&actions, &reference), json),
+        SubCommand::Run { reference, yes } => {
+            run(conn, &find_actions(&actions, &reference), config, yes)
+        }
+        SubCommand::Cmd { query } => cmd(conn, &actions, &query),
+        SubCommand::Check => check(conn),
+        SubCommand::Exec { env, command } => exec(conn, &env, &command),
+        SubCommand::Agent { .. } | SubCommand::Lock => {
+            unreachable!("handled before opening the database")
+        }
+    }
+}
+
+fn list(actions: &[Action], json: bool) {
+    let outputs: Vec<_> = actions.iter().map(action_output).collect();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&outputs).unwrap());
+    } else {
+        for output in outputs {
+            println!(
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                output.reference,
+                output.project,
+                output.environment.unwrap_or("-"),
+                output.item_type,
+                output.server.unwrap_or("-"),
+                output.description.unwrap_or("")
+            );
+        }
+    }
+}
+
+fn show(actions: &[&Action], json: bool) {
+    let item = &actions[0].item;
+    if json {
+        let output = ShowOutput {
+            reference: item_reference(item),
+            details: database::item_details(item)
+                .into_iter()
+                .map(|(label, value)| {
+                    (
+                        label.to_lowercase().replace(' ', "_"),
+                        serde_json::Value::String(value),
+                    )
+                })
+                .collect(),
+            actions: actions
+                .iter()
+                .map(|a| ShowActionOutput {
+                    action: &a.desc,
+                    command: Some(a)
+                        .filter(|a| !a.is_secret && actions::get_secret_files(a).is_empty())
+                        .map(|a| (a.get_string)(&a.item).to_string()),
+                    runnable: is_runnable(a),
+                })
+                .collect(),
+        };
+        println!("{}", serde_json::to_string_pretty(&output).unwrap());
+    } else {
+        println!("{}", item_reference(item));
+        for (label, value) in database::item_details(item) {
+            println!("{:<12} {}", format!("{}:", label), value);
+        }
+        println!();
+        for action in actions {
+            let (_, value) = database::action_value_for_display(action);
+            println!("{}: {}", action.desc, value);
+        }
+    }
+}
+
+fn run(conn: &SqliteConnection, item_actions: &[&Action], config: &Config, yes: bool) {
+    let action = item_actions
+        .iter()
+        .find(|a| is_runnable(a))
+        .unwrap_or_else(|| {
+            eprintln!("The action can't be run, it can only be copied");
+            std::process::exit(EXIT_INVALID_REFERENCE);
+        });
+    let commands = crate::run::prepare(conn, &[*action], config, DisplayMode::Color, yes)
+        .unwrap_or_else(|e| {
+            eprintln!("Failed preparing the command, aborting. {}", e);
+            std::process::exit(EXIT_PREPARE_FAILED);
+        })
+        .unwrap_or_else(|| std::process::exit(EXIT_CANCELLED));
+    let status = crate::run::run(&commands[0]).unwrap_or_else(|e| {
+        eprintln!("{}, aborting.", e);
+        std::process::exit(EXIT_SECRET_FILES_FAILED);
+    });
+    match status {
+        // killed by a signal: no exit code
+        Some(status) => std::process::exit(status.code().unwrap_or(1)),
+        None => std::process::exit(EXIT_LAUNCH_FAILED),
+    }
+}
+
+fn cmd(conn: &SqliteConnection, actions: &[Action], query: &[String]) {
+    let matches: Vec<_> = actions
+        .iter()
+        .filter(|a| is_runnable(a) && database::matches_query(a, query))
+        .collect();
+    match &matches[..] {
+        [] => {
+            eprintln!("No runnable action matches the query");
+            std::process::exit(EXIT_NO_MATCH);
+        }
+        [action] if !actions::get_secret_files(action).is_empty() => {
+            eprintln!(
+                "The command needs files with secrets (ssh key, database password...) which only exist while ppcli runs it, use: ppcli run {}",
+                action_reference(action)
+            );
+            std::process::exit(EXIT_NEEDS_SECRET_FILES);
+        }
+        [action] => match placeholders::resolve_references(
+            conn,
+            action.item.project_id,
+            &(action.get_string)(&action.item),
+        ) {
+            Ok(command) => println!("{}", command),
+            Err(e) => {
+                eprintln!("Failed resolving the references of the command: {}", e);
+                std::process::exit(EXIT_PREPARE_FAILED);
+            }
+        },
+        _ => {
+            eprintln!("Several actions match the query:");
+            for action in &matches {
+                eprintln!("{}", action_reference(action));
+            }
+            std::process::exit(EXIT_SEVERAL_MATCHES);
+        }
+    }
+}
+
+fn check(conn: &SqliteConnection) {
+    let broken = placeholders::find_broken_references(conn).unwrap_or_else(|e| {
+        eprintln!("Error reading the database: {}", e);
+        std::process::exit(EXIT_BROKEN_REFERENCES);
+    });
+    for entry in &broken {
+        println!("{}", entry);
+    }
+    if !broken.is_empty() {
+        std::process::exit(EXIT_BROKEN_REFERENCES);
+    }
+}
+
+fn exec(conn: &SqliteConnection, env: &[String], command: &[String]) {
+    let vars: Vec<_> = env
+        .iter()
+        .map(|var| {
+            resolve_env_var(conn, var).unwrap_or_else(|(e, exit_code)| {
+                eprintln!("{}", e);
+                std::process::exit(exit_code);
+            })
+        })
+        .collect();
+    match std::process::Command::new(&command[0])
+        .args(&command[1..])
+        .envs(vars)
+        .status()
+    {
+        // killed by a signal: no exit code
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("Error launching {}: {}", command[0], e);
+            std::process::exit(EXIT_LAUNCH_FAILED);
+        }
+    }
+}
+
+/// Get the value for a variable like PGPASSWORD=db:12.password
+fn resolve_env_var<'a>(
+    conn: &SqliteConnection,
+    var: &'a str,
+) -> Result<(&'a str, String), (String, i32)> {
+    let invalid = || {
+        (
+            format!(
+                "Invalid variable {}, expected NAME=<server|db|www>:<id>.<field>",
+                var
+            ),
+            EXIT_INVALID_REFERENCE,
+        )
+    };
+    let mut parts = var.splitn(2, '=');
+    let (name, reference) = match (parts.next(), parts.next()) {
+        (Some(name), Some(reference)) if !name.is_empty() => (name, reference),
+        _ => return Err(invalid()),
+    };
+    let mut parts = reference.splitn(2, ':');
+    let (kind, item) = match (parts.next(), parts.next()) {
+        (Some(kind), Some(item)) => (kind, item),
+        _ => return Err(invalid()),
+    };
+    let kind = ReferenceKind::from_prefix(kind).ok_or_else(invalid)?;
+    let mut parts = item.splitn(2, '.');
+    let (id, field) = match (parts.next().map(|id| id.parse::<i32>()), parts.next()) {
+        (Some(Ok(id)), Some(field)) => (id, field),
+        _ => return Err(invalid()),
+    };
+    placeholders::check_field(kind, field)
+        .map_err(|e| (format!("{}: {}", var, e), EXIT_INVALID_REFERENCE))?;
+    placeholders::resolve_field_by_id(conn, kind, id, field)
+        .map(|value| (name, value))
+        .map_err(|e| (format!("{}: {}", var, e), EXIT_NO_MATCH))
+}
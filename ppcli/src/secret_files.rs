@@ -0,0 +1,130 @@
+// some secrets stored in the database (for instance ssh private keys) must
+// be handed to the processes we launch through files. We write them in a
+// folder that only the current user can access, and the files themselves
+// are readable only by the current user. They're removed when the process exits.
+// Their names are unique to each ppcli run: several runs may be going on at the
+// same time, and one run mustn't remove the files another one still needs.
+use std::fs::{DirBuilder, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+// picked at the first use, then the same for the whole run
+static RUN_NONCE: AtomicU64 = AtomicU64::new(0);
+
+pub struct SecretFile {
+    pub path: PathBuf,
+    pub contents: Vec<u8>,
+    pub executable: bool,
+}
+
+pub fn private_folder() -> PathBuf {
+    // prefer $XDG_RUNTIME_DIR, it's a tmpfs which is private to the user
+    // and cleared on logout. Fallback on the temp folder with a per-user
+    // subfolder.
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) if !runtime_dir.is_empty() => {
+            let mut path = PathBuf::from(runtime_dir);
+            path.push("ppcli");
+            path
+        }
+        _ => {
+            let mut path = std::env::temp_dir();
+            path.push(format!("ppcli-{}", unsafe { libc::getuid() }));
+            path
+        }
+    }
+}
+
+pub fn private_file_path(file_name: &str) -> PathBuf {
+    let mut path = private_folder();
+    path.push(file_name);
+    path
+}
+
+fn run_nonce() -> u64 {
+    let nonce = RUN_NONCE.load(Ordering::SeqCst);
+    if nonce != 0 {
+        return nonce;
+    }
+    let new_nonce = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+        | 1;
+    match RUN_NONCE.compare_exchange(0, new_nonce, Ordering::SeqCst, Ordering::SeqCst) {
+        Ok(_) => new_nonce,
+        Err(other_thread_nonce) => other_thread_nonce,
+    }
+}
+
+/// The path of a secret file for this run of ppcli only
+pub fn run_file_path(file_name: &str) -> PathBuf {
+    private_file_path(&format!(
+        "{}-{:x}-{}",
+        std::process::id(),
+        run_nonce(),
+        file_name
+    ))
+}
+
+pub fn create_private_folder() -> std::io::Result<()> {
+    let folder = private_folder();
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&folder)?;
+    // the folder may have been there already. In the temp folder, another
+    // user could have created it to read or swap our files: check that it's ours.
+    let metadata = std::fs::symlink_metadata(&folder)?;
+    if !metadata.file_type().is_dir()
+        || metadata.uid() != unsafe { libc::getuid() }
+        || metadata.mode() & 0o777 != 0o700
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!(
+                "{} must be a folder owned by the current user, with permissions 700",
+                folder.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub fn write_secret_files(files: &[SecretFile]) -> std::io::Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+    create_private_folder()?;
+    for file in files {
+        write_secret_file(
+            &file.path,
+            &file.contents,
+            if file.executable { 0o700 } else { 0o600 },
+        )?;
+    }
+    Ok(())
+}
+
+fn write_secret_file(path: &Path, contents: &[u8], mode: u32) -> std::io::Result<()> {
+    // the file may be left over from a previous run which didn't clean up,
+    // remove it so that we're sure the permissions are the ones we set.
+    let _ = std::fs::remove_file(path);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(path)?;
+    file.write_all(contents)
+}
+
+pub fn remove_secret_files(files: &[SecretFile]) {
+    for file in files {
+        if let Err(e) = std::fs::remove_file(&file.path) {
+            eprintln!("Failed removing the temporary file {:?}: {}", file.path, e);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+// the password of the database. If `ppcli agent` runs, it gives us the raw key instead.
+// Otherwise the OS keyring is tried first. Where there is none (headless machines,
+// plain ssh sessions, containers), we fall back on a file descriptor, a file,
+// a command such as pass or gpg, and finally we ask the user on the terminal.
+use crate::config::Config;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::mem::ManuallyDrop;
+use std::os::unix::io::FromRawFd;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub const PASSWORD_FILE_ENV_VAR: &str = "PROJECTPAD_PASSWORD_FILE";
+
+pub fn get_db_password(password_fd: Option<i32>, config: &Config) -> Result<String, String> {
+    // the raw key unlocks the database as well as the password
+    if let Some(db_key) = crate::agent::get_db_key() {
+        return Ok(db_key);
+    }
+    let keyring_error = match crate::secretservice::get_keyring_pass() {
+        Ok(Some(pass)) => return Ok(pass),
+        Ok(None) => "no matching credentials in the OS keyring".to_string(),
+        Err(e) => format!("no OS keyring ({})", e),
+    };
+    if let Some(fd) = password_fd {
+        return read_password_fd(fd);
+    }
+    if let Some(path) = std::env::var_os(PASSWORD_FILE_ENV_VAR) {
+        return File::open(&path)
+            .and_then(read_first_line)
+            .map_err(|e| format!("Error reading {}: {}", Path::new(&path).display(), e));
+    }
+    if let Some(command) = &config.password_command {
+        return run_password_command(command);
+    }
+    crate::prompt::read_password_from_tty("Projectpad database password: ").map_err(|e| {
+        format!(
+            "{}, and cannot ask for the password on the terminal: {}",
+            keyring_error, e
+        )
+    })
+}
+
+fn read_password_fd(fd: i32) -> Result<String, String> {
+    // stdin, stdout and stderr are for skim and the shell integration
+    if fd < 3 {
+        return Err(format!(
+            "The password file descriptor must be 3 or more, got {}",
+            fd
+        ));
+    }
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(format!(
+            "Error reading the file descriptor {}: {}",
+            fd,
+            std::io::Error::last_os_error()
+        ));
+    }
+    // the file descriptor belongs to the caller, don't close it
+    let file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+    read_first_line(&*file).map_err(|e| format!("Error reading the file descriptor {}: {}", fd, e))
+}
+
+// the password is the first line, as with pass
+fn read_first_line(file: impl Read) -> std::io::Result<String> {
+    let mut line = String::new();
+    BufReader::new(file).read_line(&mut line)?;
+    Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+fn run_password_command(command: &str) -> Result<String, String> {
+    let cl_elts = shell_words::split(command)
+        .ok()
+        .filter(|elts| !elts.is_empty())
+        .ok_or_else(|| format!("Couldn't parse the password command: {}", command))?;
+    // stdin and stderr are the terminal's, for gpg's pinentry for instance
+    let output = Command::new(&cl_elts[0])
+        .args(cl_elts.iter().skip(1))
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("Error launching the password command {}: {}", command, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "The password command {} failed: {}",
+            command, output.status
+        ));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|l| l.to_string())
+        .ok_or_else(|| format!("The password command {} printed nothing", command))
+}
@@ -205,6 +205,10 @@ impl Widget for ServerPoiAddEditDialog {
         let new_desc = self.desc_entry.get_text();
         let new_path = self.path_entry.get_text();
         let new_text = self.text_entry.get_text();
+        if let Err(e) = projectpadsql::placeholders::parse_placeholders(&new_text) {
+            standard_dialogs::display_error_str("Invalid placeholder in the text", Some(e));
+            return;
+        }
         let new_group = self.group.get_active_text();
         let new_interest_type = self.combo_read_interest_type();
         let new_run_on = self
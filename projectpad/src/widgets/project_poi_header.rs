@@ -52,6 +52,7 @@ pub enum Msg {
     ProjectItemUpdated(Option<ProjectItem>),
     GotoItem(Project, Server),
     ShowInfoBar(String),
+    CopyResolved(String),
     CopyPassword,
     OpenLinkOrEditProjectNote,
     OpenSingleWebsiteLink,
@@ -62,6 +63,9 @@ type DeleteResult = Result<ProjectItem, (&'static str, Option<String>)>;
 
 type GotoResult = (Project, Server);
 
+// the text to copy, after resolving references like {{server:"DB prod".ip}}
+type CopyResult = Result<String, String>;
+
 pub struct Model {
     relm: relm::Relm<ProjectPoiHeader>,
     db_sender: mpsc::Sender<SqlFunc>,
@@ -75,6 +79,8 @@ pub struct Model {
     project_item_deleted_sender: relm::Sender<DeleteResult>,
     _goto_server_channel: relm::Channel<GotoResult>,
     goto_server_sender: relm::Sender<GotoResult>,
+    _copy_resolved_channel: relm::Channel<CopyResult>,
+    copy_resolved_sender: relm::Sender<CopyResult>,
 }
 
 #[derive(Debug)]
@@ -342,6 +348,14 @@ impl Widget for ProjectPoiHeader {
         let stream2 = relm.stream().clone();
         let (_goto_server_channel, goto_server_sender) =
             relm::Channel::new(move |r: GotoResult| stream2.emit(Msg::GotoItem(r.0.clone(), r.1)));
+        let stream3 = relm.stream().clone();
+        let (_copy_resolved_channel, copy_resolved_sender) =
+            relm::Channel::new(move |r: CopyResult| match r {
+                Ok(val) => stream3.emit(Msg::CopyResolved(val)),
+                Err(e) => {
+                    standard_dialogs::display_error_str("Failed resolving a reference", Some(e))
+                }
+            });
         Model {
             relm: relm.clone(),
             db_sender,
@@ -359,6 +373,8 @@ impl Widget for ProjectPoiHeader {
             project_item_deleted_sender,
             _goto_server_channel,
             goto_server_sender,
+            _copy_resolved_channel,
+            copy_resolved_sender,
         }
     }
 
@@ -379,6 +395,30 @@ impl Widget for ProjectPoiHeader {
                 self.load_project_item();
             }
             Msg::HeaderActionClicked((ActionTypes::Copy, val)) => {
+                let project_id = match &self.model.project_item {
+                    Some(ProjectItem::Server(srv)) => Some(srv.project_id),
+                    Some(ProjectItem::ServerLink(l)) => Some(l.project_id),
+                    Some(ProjectItem::ProjectNote(n)) => Some(n.project_id),
+                    Some(ProjectItem::ProjectPointOfInterest(poi)) => Some(poi.project_id),
+                    None => None,
+                };
+                match project_id {
+                    Some(project_id) if projectpadsql::placeholders::has_references(&val) => {
+                        let s = self.model.copy_resolved_sender.clone();
+                        self.model
+                            .db_sender
+                            .send(SqlFunc::new(move |sql_conn| {
+                                s.send(projectpadsql::placeholders::resolve_references(
+                                    sql_conn, project_id, &val,
+                                ))
+                                .unwrap();
+                            }))
+                            .unwrap();
+                    }
+                    _ => self.copy_to_clipboard(&val),
+                }
+            }
+            Msg::CopyResolved(val) => {
                 self.copy_to_clipboard(&val);
             }
             Msg::HeaderActionClicked((ActionTypes::GotoItem, _val)) => {
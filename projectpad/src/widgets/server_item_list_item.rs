@@ -31,6 +31,7 @@ use std::sync::mpsc;
 #[derive(Msg, Clone)]
 pub enum Msg {
     CopyClicked(String),
+    CopyResolved(String),
     ViewNote(ServerNote),
     EditNote(ServerNote),
     EditPoi(ServerPointOfInterest),
@@ -53,6 +54,9 @@ pub enum Msg {
 // String for details, because I can't pass Error across threads
 type DeleteResult = Result<ServerItem, (&'static str, Option<String>)>;
 
+// the text to copy, after resolving references like {{server:"DB prod".ip}}
+type CopyResult = Result<String, String>;
+
 pub struct Model {
     relm: relm::Relm<ServerItemListItem>,
     db_sender: mpsc::Sender<SqlFunc>,
@@ -64,6 +68,8 @@ pub struct Model {
     title: (String, Icon),
     _server_item_deleted_channel: relm::Channel<DeleteResult>,
     server_item_deleted_sender: relm::Sender<DeleteResult>,
+    _copy_resolved_channel: relm::Channel<CopyResult>,
+    copy_resolved_sender: relm::Sender<CopyResult>,
 }
 
 pub fn get_server_item_grid_items(
@@ -461,6 +467,14 @@ impl Widget for ServerItemListItem {
                 }
                 Err((msg, e)) => standard_dialogs::display_error_str(&msg, e),
             });
+        let stream2 = relm.stream().clone();
+        let (_copy_resolved_channel, copy_resolved_sender) =
+            relm::Channel::new(move |r: CopyResult| match r {
+                Ok(val) => stream2.emit(Msg::CopyResolved(val)),
+                Err(e) => {
+                    standard_dialogs::display_error_str("Failed resolving a reference", Some(e))
+                }
+            });
         Model {
             relm: relm.clone(),
             db_sender,
@@ -472,6 +486,8 @@ impl Widget for ServerItemListItem {
             header_popover: gtk::Popover::new(None::<&gtk::Button>),
             _server_item_deleted_channel,
             server_item_deleted_sender,
+            _copy_resolved_channel,
+            copy_resolved_sender,
         }
     }
 
@@ -496,7 +512,30 @@ impl Widget for ServerItemListItem {
 
     fn update(&mut self, event: Msg) {
         match event {
-            Msg::CopyClicked(val) => {
+            Msg::CopyClicked(val) if projectpadsql::placeholders::has_references(&val) => {
+                let server_id = self.model.server_item.server_id();
+                let s = self.model.copy_resolved_sender.clone();
+                self.model
+                    .db_sender
+                    .send(SqlFunc::new(move |sql_conn| {
+                        use projectpadsql::schema::server::dsl as srv;
+                        s.send(
+                            srv::server
+                                .find(server_id)
+                                .select(srv::project_id)
+                                .first::<i32>(sql_conn)
+                                .map_err(|e| e.to_string())
+                                .and_then(|project_id| {
+                                    projectpadsql::placeholders::resolve_references(
+                                        sql_conn, project_id, &val,
+                                    )
+                                }),
+                        )
+                        .unwrap();
+                    }))
+                    .unwrap();
+            }
+            Msg::CopyClicked(val) | Msg::CopyResolved(val) => {
                 if let Some(clip) = gtk::Clipboard::get_default(&self.items_grid.get_display()) {
                     clip.set_text(&val);
                 }
@@ -2,6 +2,7 @@
 extern crate diesel;
 
 pub mod models;
+pub mod placeholders;
 pub mod schema;
 
 use diesel::expression::AsExpression;
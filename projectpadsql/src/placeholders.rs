@@ -0,0 +1,496 @@
+// placeholders in POI commands, like {{ask:customer_id}} or {{server:"DB prod".ip}},
+// shared between the GUI (which validates them) and ppcli (which replaces them).
+// Only the {{kind:...}} forms listed in PLACEHOLDER_KINDS are placeholders,
+// so that commands such as docker ps --format '{{.Names}}' are left alone.
+use crate::models::{Server, ServerDatabase, ServerWebsite};
+use diesel::prelude::*;
+use std::ops::Range;
+
+const PLACEHOLDER_KINDS: &[&str] = &["ask", "server", "db", "www"];
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Placeholder {
+    // prompt the user for a value when running the command
+    Ask {
+        name: String,
+        default: Option<String>,
+    },
+    // a field of another item of the same project, looked up by its
+    // description when the command is used, so that it follows edits
+    Reference {
+        kind: ReferenceKind,
+        name: String,
+        field: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ReferenceKind {
+    Server,
+    Database,
+    Website,
+}
+
+impl ReferenceKind {
+    /// The kind for a prefix like the "db" of {{db:main.password}}
+    pub fn from_prefix(prefix: &str) -> Option<ReferenceKind> {
+        match prefix {
+            "server" => Some(ReferenceKind::Server),
+            "db" => Some(ReferenceKind::Database),
+            "www" => Some(ReferenceKind::Website),
+            _ => None,
+        }
+    }
+
+    fn fields(self) -> &'static [&'static str] {
+        match self {
+            ReferenceKind::Server => &["desc", "ip", "username", "password", "text"],
+            ReferenceKind::Database => &["desc", "name", "username", "password", "text"],
+            ReferenceKind::Website => &["desc", "url", "username", "password", "text"],
+        }
+    }
+
+    fn item_desc(self) -> &'static str {
+        match self {
+            ReferenceKind::Server => "server",
+            ReferenceKind::Database => "database",
+            ReferenceKind::Website => "website",
+        }
+    }
+}
+
+pub fn parse_placeholders(text: &str) -> Result<Vec<(Range<usize>, Placeholder)>, String> {
+    let mut result = vec![];
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find("{{") {
+        let start = pos + offset;
+        let contents_start = start + "{{".len();
+        let kind = PLACEHOLDER_KINDS
+            .iter()
+            .find(|k| text[contents_start..].starts_with(&format!("{}:", k)));
+        let kind = match kind {
+            Some(kind) => kind,
+            None => {
+                pos = contents_start;
+                continue;
+            }
+        };
+        let contents_end = text[contents_start..]
+            .find("}}")
+            .map(|e| contents_start + e)
+            .ok_or_else(|| format!("Unclosed placeholder: {}", &text[start..]))?;
+        let spec = &text[contents_start + kind.len() + 1..contents_end];
+        let placeholder = match *kind {
+            "ask" => parse_ask(spec)?,
+            kind => parse_reference(ReferenceKind::from_prefix(kind).unwrap(), spec)?,
+        };
+        pos = contents_end + "}}".len();
+        result.push((start..pos, placeholder));
+    }
+    Ok(result)
+}
+
+fn parse_ask(spec: &str) -> Result<Placeholder, String> {
+    let mut parts = spec.split('|');
+    let name = parts.next().unwrap_or("").trim();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!(
+            "Invalid parameter name '{}' in {{{{ask:{}}}}}, expected letters, digits, '-' or '_'",
+            name, spec
+        ));
+    }
+    let mut default = None;
+    for option in parts {
+        match option.splitn(2, '=').collect::<Vec<_>>()[..] {
+            [key, value] if key.trim() == "default" => default = Some(value.trim().to_string()),
+            _ => {
+                return Err(format!(
+                    "Unknown option '{}' for the parameter {}, expected default=<value>",
+                    option, name
+                ))
+            }
+        }
+    }
+    Ok(Placeholder::Ask {
+        name: name.to_string(),
+        default,
+    })
+}
+
+// the name is quoted if it contains dots: {{db:"main".password}} or {{db:main.password}}
+fn parse_reference(kind: ReferenceKind, spec: &str) -> Result<Placeholder, String> {
+    let (name, field) = if let Some(quoted) = spec.strip_prefix('"') {
+        let name_end = quoted
+            .find('"')
+            .ok_or_else(|| format!("Unclosed quote in the reference {}", spec))?;
+        let field = quoted[name_end + 1..]
+            .strip_prefix('.')
+            .ok_or_else(|| format!("Expected .<field> after the name in the reference {}", spec))?;
+        (&quoted[..name_end], field)
+    } else {
+        let dot = spec
+            .rfind('.')
+            .ok_or_else(|| format!("Expected <name>.<field> in the reference {}", spec))?;
+        (&spec[..dot], &spec[dot + 1..])
+    };
+    if name.is_empty() {
+        return Err(format!("Missing name in the reference {}", spec));
+    }
+    check_field(kind, field).map_err(|e| format!("{} in the reference {}", e, spec))?;
+    Ok(Placeholder::Reference {
+        kind,
+        name: name.to_string(),
+        field: field.to_string(),
+    })
+}
+
+pub fn check_field(kind: ReferenceKind, field: &str) -> Result<(), String> {
+    if kind.fields().contains(&field) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Unknown field '{}', a {} has: {}",
+            field,
+            kind.item_desc(),
+            kind.fields().join(", ")
+        ))
+    }
+}
+
+/// Replace the placeholders in the text by the values that get_value returns.
+/// get_value also gets the source text of the placeholder, to leave it as is.
+pub fn replace_placeholders(
+    text: &str,
+    mut get_value: impl FnMut(&Placeholder, &str) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut result = String::new();
+    let mut pos = 0;
+    for (range, placeholder) in parse_placeholders(text)? {
+        result.push_str(&text[pos..range.start]);
+        result.push_str(&get_value(&placeholder, &text[range.clone()])?);
+        pos = range.end;
+    }
+    result.push_str(&text[pos..]);
+    Ok(result)
+}
+
+/// "today" as a default value stands for the current date
+pub fn resolve_ask_default(default: &str) -> String {
+    if default == "today" {
+        chrono::Local::today().format("%Y-%m-%d").to_string()
+    } else {
+        default.to_string()
+    }
+}
+
+pub fn has_references(text: &str) -> bool {
+    parse_placeholders(text).is_ok_and(|placeholders| {
+        placeholders
+            .iter()
+            .any(|(_, p)| matches!(p, Placeholder::Reference { .. }))
+    })
+}
+
+// copying such a text puts a password in the clipboard
+pub fn has_secret_references(text: &str) -> bool {
+    parse_placeholders(text).is_ok_and(|placeholders| {
+        placeholders
+            .iter()
+            .any(|(_, p)| matches!(p, Placeholder::Reference { field, .. } if field == "password"))
+    })
+}
+
+/// Replace the references to other items of the project by their current
+/// value, leaving the {{ask:...}} parameters for the caller.
+pub fn resolve_references(
+    sql_conn: &SqliteConnection,
+    project_id: i32,
+    text: &str,
+) -> Result<String, String> {
+    replace_placeholders(text, |placeholder, source| match placeholder {
+        Placeholder::Ask { .. } => Ok(source.to_string()),
+        Placeholder::Reference { kind, name, field } => {
+            resolve_reference(sql_conn, project_id, *kind, name, field)
+        }
+    })
+}
+
+/// The passwords of references are given to commands through the environment
+/// variables PPCLI_REF_1, PPCLI_REF_2...
+pub const REFERENCE_ENV_VAR_PREFIX: &str = "PPCLI_REF_";
+
+/// Like resolve_references, but the passwords are replaced by ${PPCLI_REF_n}
+/// in the command, and returned separately as the environment variables to
+/// set for it. That way they're not displayed, nor saved in the shell history.
+pub fn resolve_references_secrets_to_env(
+    sql_conn: &SqliteConnection,
+    project_id: i32,
+    text: &str,
+) -> Result<(String, Vec<(String, String)>), String> {
+    let mut env = vec![];
+    let command = replace_placeholders(text, |placeholder, source| match placeholder {
+        Placeholder::Ask { .. } => Ok(source.to_string()),
+        Placeholder::Reference { kind, name, field } => {
+            let value = resolve_reference(sql_conn, project_id, *kind, name, field)?;
+            if field == "password" {
+                let var_name = format!("{}{}", REFERENCE_ENV_VAR_PREFIX, env.len() + 1);
+                let var_ref = format!("${{{}}}", var_name);
+                env.push((var_name, value));
+                Ok(var_ref)
+            } else {
+                Ok(value)
+            }
+        }
+    })?;
+    Ok((command, env))
+}
+
+fn resolve_reference(
+    sql_conn: &SqliteConnection,
+    project_id: i32,
+    kind: ReferenceKind,
+    name: &str,
+    field: &str,
+) -> Result<String, String> {
+    use crate::schema::server::dsl as srv;
+    use crate::schema::server_database::dsl as db;
+    use crate::schema::server_website::dsl as www;
+    let values: Vec<String> = match kind {
+        ReferenceKind::Server => srv::server
+            .filter(srv::project_id.eq(project_id).and(srv::desc.eq(name)))
+            .load::<Server>(sql_conn)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|s| server_field(s, field))
+            .collect(),
+        ReferenceKind::Database => db::server_database
+            .inner_join(srv::server)
+            .select(db::server_database::all_columns())
+            .filter(srv::project_id.eq(project_id).and(db::desc.eq(name)))
+            .load::<ServerDatabase>(sql_conn)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|d| database_field(d, field))
+            .collect(),
+        ReferenceKind::Website => www::server_website
+            .inner_join(srv::server)
+            .select(www::server_website::all_columns())
+            .filter(srv::project_id.eq(project_id).and(www::desc.eq(name)))
+            .load::<ServerWebsite>(sql_conn)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|w| website_field(w, field))
+            .collect(),
+    };
+    match &values[..] {
+        [value] => Ok(value.clone()),
+        [] => Err(format!(
+            "No {} named '{}' in the project",
+            kind.item_desc(),
+            name
+        )),
+        _ => Err(format!(
+            "Several {}s are named '{}' in the project",
+            kind.item_desc(),
+            name
+        )),
+    }
+}
+
+/// The value of a field of an item given by its id, for instance the
+/// password of the database 12.
+pub fn resolve_field_by_id(
+    sql_conn: &SqliteConnection,
+    kind: ReferenceKind,
+    id: i32,
+    field: &str,
+) -> Result<String, String> {
+    use crate::schema::server::dsl as srv;
+    use crate::schema::server_database::dsl as db;
+    use crate::schema::server_website::dsl as www;
+    check_field(kind, field)?;
+    match kind {
+        ReferenceKind::Server => srv::server
+            .find(id)
+            .first::<Server>(sql_conn)
+            .map(|s| server_field(s, field)),
+        ReferenceKind::Database => db::server_database
+            .find(id)
+            .first::<ServerDatabase>(sql_conn)
+            .map(|d| database_field(d, field)),
+        ReferenceKind::Website => www::server_website
+            .find(id)
+            .first::<ServerWebsite>(sql_conn)
+            .map(|w| website_field(w, field)),
+    }
+    .map_err(|e| match e {
+        diesel::result::Error::NotFound => format!("No {} with id {}", kind.item_desc(), id),
+        e => e.to_string(),
+    })
+}
+
+fn server_field(s: Server, field: &str) -> String {
+    match field {
+        "ip" => s.ip,
+        "username" => s.username,
+        "password" => s.password,
+        "text" => s.text,
+        _ => s.desc,
+    }
+}
+
+fn database_field(d: ServerDatabase, field: &str) -> String {
+    match field {
+        "name" => d.name,
+        "username" => d.username,
+        "password" => d.password,
+        "text" => d.text,
+        _ => d.desc,
+    }
+}
+
+fn website_field(w: ServerWebsite, field: &str) -> String {
+    match field {
+        "url" => w.url,
+        "username" => w.username,
+        "password" => w.password,
+        "text" => w.text,
+        _ => w.desc,
+    }
+}
+
+/// List the entries whose placeholders are invalid, or reference items
+/// which don't exist anymore (for instance after a rename).
+pub fn find_broken_references(sql_conn: &SqliteConnection) -> Result<Vec<String>, String> {
+    use crate::schema::project::dsl as prj;
+    use crate::schema::project_note::dsl as prj_note;
+    use crate::schema::project_point_of_interest::dsl as prj_poi;
+    use crate::schema::server::dsl as srv;
+    use crate::schema::server_note::dsl as srv_note;
+    use crate::schema::server_point_of_interest::dsl as srv_poi;
+    // (project, entry type, entry description, text, project id)
+    let mut entries: Vec<(String, &str, String, String, i32)> = vec![];
+    let to_entries = |kind: &'static str| {
+        move |(project, desc, text, project_id): (String, String, String, i32)| {
+            (project, kind, desc, text, project_id)
+        }
+    };
+    entries.extend(
+        srv_poi::server_point_of_interest
+            .inner_join(srv::server.inner_join(prj::project))
+            .select((prj::name, srv_poi::desc, srv_poi::text, srv::project_id))
+            .load::<(String, String, String, i32)>(sql_conn)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(to_entries("server point of interest")),
+    );
+    entries.extend(
+        prj_poi::project_point_of_interest
+            .inner_join(prj::project)
+            .select((prj::name, prj_poi::desc, prj_poi::text, prj_poi::project_id))
+            .load::<(String, String, String, i32)>(sql_conn)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(to_entries("project point of interest")),
+    );
+    entries.extend(
+        srv_note::server_note
+            .inner_join(srv::server.inner_join(prj::project))
+            .select((
+                prj::name,
+                srv_note::title,
+                srv_note::contents,
+                srv::project_id,
+            ))
+            .load::<(String, String, String, i32)>(sql_conn)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(to_entries("server note")),
+    );
+    entries.extend(
+        prj_note::project_note
+            .inner_join(prj::project)
+            .select((
+                prj::name,
+                prj_note::title,
+                prj_note::contents,
+                prj_note::project_id,
+            ))
+            .load::<(String, String, String, i32)>(sql_conn)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(to_entries("project note")),
+    );
+    Ok(entries
+        .into_iter()
+        .filter_map(|(project, kind, desc, text, project_id)| {
+            resolve_references(sql_conn, project_id, &text)
+                .err()
+                .map(|e| format!("{}: {} '{}': {}", project, kind, desc, e))
+        })
+        .collect())
+}
+
+#[test]
+fn parse_ask_placeholders() {
+    assert_eq!(
+        Ok(vec![
+            (
+                8..27,
+                Placeholder::Ask {
+                    name: "customer_id".to_string(),
+                    default: None
+                }
+            ),
+            (
+                57..83,
+                Placeholder::Ask {
+                    name: "date".to_string(),
+                    default: Some("today".to_string())
+                }
+            )
+        ]),
+        parse_placeholders(
+            "export -{{ask:customer_id}} --format '{{.Names}}' --date {{ask:date|default=today}}"
+        )
+    );
+}
+
+#[test]
+fn parse_invalid_ask_placeholders() {
+    assert!(parse_placeholders("run {{ask:customer id}}").is_err());
+    assert!(parse_placeholders("run {{ask:id|defaut=1}}").is_err());
+    assert!(parse_placeholders("run {{ask:id").is_err());
+}
+
+#[test]
+fn parse_reference_placeholders() {
+    assert_eq!(
+        Ok(vec![
+            (
+                8..31,
+                Placeholder::Reference {
+                    kind: ReferenceKind::Server,
+                    name: "DB prod".to_string(),
+                    field: "ip".to_string()
+                }
+            ),
+            (
+                35..55,
+                Placeholder::Reference {
+                    kind: ReferenceKind::Database,
+                    name: "main".to_string(),
+                    field: "password".to_string()
+                }
+            )
+        ]),
+        parse_placeholders("psql -h {{server:\"DB prod\".ip}} -W {{db:main.password}}")
+    );
+    assert!(parse_placeholders("ssh {{server:\"DB prod\".port}}").is_err());
+    assert!(parse_placeholders("ssh {{server:\"DB prod.ip}}").is_err());
+    assert!(parse_placeholders("ssh {{www:site}}").is_err());
+}